@@ -1,8 +1,54 @@
-use clap::{Parser, Subcommand};
+use crate::{logger::LogFormat, multiplexer::MultiplexerKind, sandbox::SandboxMode};
+use clap::{Args, Parser, Subcommand};
+use ebay_authd_client::discovery::{CONFIG_ENV, SOCKET_ENV};
+use log::LevelFilter;
+use std::{num::ParseIntError, path::PathBuf, time::Duration};
 
 #[derive(Debug, Parser)]
 #[command(version, about, long_about)]
-pub enum Cli {
+pub struct Cli {
+    /// Configuration file (YAML, TOML or JSON)
+    #[arg(long, global = true, value_name = "PATH", env = CONFIG_ENV)]
+    pub config: Option<PathBuf>,
+
+    /// Socket path, or `@name` for a Linux abstract socket
+    #[arg(long, global = true, env = SOCKET_ENV)]
+    pub socket: Option<String>,
+
+    #[command(flatten)]
+    pub log: LogArgs,
+
+    #[command(subcommand)]
+    pub command: Command,
+}
+
+#[derive(Debug, Args)]
+pub struct LogArgs {
+    /// Log level (off, error, warn, info, debug or trace), overrides the
+    /// configuration
+    #[arg(long, global = true, value_name = "LEVEL")]
+    pub log_level: Option<LevelFilter>,
+    /// Log output format
+    #[arg(long, global = true, value_enum, default_value_t)]
+    pub log_format: LogFormat,
+    /// Write the log to this file, defaults to a file next to the socket with
+    /// `daemon start --detach`
+    #[arg(long, global = true, value_name = "PATH", conflicts_with = "journald")]
+    pub log_file: Option<PathBuf>,
+    /// Rotate the log file once it reaches this size (`K`, `M` and `G`
+    /// suffixes are allowed)
+    #[arg(long, global = true, value_name = "SIZE", default_value = "10M", value_parser = parse_size)]
+    pub log_max_size: u64,
+    /// Number of rotated log files to keep
+    #[arg(long, global = true, value_name = "COUNT", default_value_t = 5)]
+    pub log_keep: usize,
+    /// Send the log to the systemd journal
+    #[arg(long, global = true)]
+    pub journald: bool,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum Command {
     /// Daemon control commands
     Daemon {
         #[command(subcommand)]
@@ -13,26 +59,217 @@ pub enum Cli {
         #[command(subcommand)]
         command: TestCommand,
     },
+    /// Authorization commands
+    Auth {
+        #[command(subcommand)]
+        command: AuthCommand,
+    },
+    /// Configuration commands
+    Config {
+        #[command(subcommand)]
+        command: ConfigCommand,
+    },
 }
 
 #[derive(Debug, Subcommand)]
-pub enum DaemonCommand {
-    /// Start the daemon
-    Start {
-        /// Utilize pre-installed screen utility
+pub enum ConfigCommand {
+    /// Create the configuration file interactively
+    Init {
+        /// Overwrite an existing configuration file
+        #[arg(long)]
+        force: bool,
+    },
+    /// Check the configuration for problems
+    Validate,
+    /// Print the effective configuration with secrets redacted
+    Show,
+    /// Upgrade the configuration file to the current layout
+    Migrate {
+        /// Replace the file (keeping a backup) instead of printing the result
         #[arg(long)]
-        screen: bool,
+        write: bool,
     },
+}
+
+#[derive(Debug, Subcommand)]
+pub enum DaemonCommand {
+    /// Start the daemon
+    Start(StartArgs),
     /// Get the status of the daemon
     Status,
-    /// Fix a broken daemon instance
-    Reauth,
+    /// Authorize the running daemon again, replacing its grant
+    Reauth(ReauthArgs),
+    /// Refresh the token of the running daemon now
+    Refresh,
+    /// Reload the configuration of the running daemon
+    Reload,
+    /// Change the log level of the running daemon
+    LogLevel {
+        /// New level (off, error, warn, info, debug or trace)
+        level: LevelFilter,
+    },
     /// Ask the daemon to stop
     Stop,
 }
 
+#[derive(Debug, Args)]
+pub struct StartArgs {
+    /// Utilize pre-installed screen utility (same as `--multiplexer screen`)
+    #[arg(long, conflicts_with_all = ["detach", "multiplexer"])]
+    pub screen: bool,
+    /// Run inside a terminal multiplexer session
+    #[arg(long, value_enum, conflicts_with = "detach")]
+    pub multiplexer: Option<MultiplexerKind>,
+    /// Run in the background once authenticated
+    #[arg(long)]
+    pub detach: bool,
+    /// Start without authenticating, authorize later using `auth login`
+    #[arg(long)]
+    pub headless: bool,
+    /// Receive the consent redirect on this address instead of asking for it
+    #[arg(long, value_name = "ADDRESS", conflicts_with = "headless")]
+    pub listen: Option<String>,
+    /// Open the consent URL in the default browser
+    #[arg(long, conflicts_with = "headless")]
+    pub open: bool,
+    /// Revoke the grant when the daemon stops
+    #[arg(long)]
+    pub revoke_on_stop: bool,
+    /// Start from an existing refresh token read from this file (`-` for
+    /// standard input) instead of authorizing
+    #[arg(long, value_name = "PATH", conflicts_with_all = ["listen", "open"])]
+    pub refresh_token_file: Option<PathBuf>,
+    /// Restrict the running daemon with Landlock and seccomp (Linux only)
+    #[arg(long, value_enum, value_name = "MODE", default_value_t)]
+    pub sandbox: SandboxMode,
+    /// Serve clients as this user once the socket is set up (requires root),
+    /// the refresh helper keeps running as the current user
+    #[arg(long, value_name = "NAME")]
+    pub user: Option<String>,
+    #[command(flatten)]
+    pub qr: QrArgs,
+}
+
+#[derive(Debug, Args)]
+pub struct ReauthArgs {
+    /// Open the consent URL in the default browser
+    #[arg(long)]
+    pub open: bool,
+    #[command(flatten)]
+    pub qr: QrArgs,
+}
+
+#[derive(Debug, Args)]
+pub struct QrArgs {
+    /// Also show the consent URL as a QR code
+    #[arg(long)]
+    pub qr: bool,
+    /// Write the consent URL as a QR code image (`.png` or `.svg`)
+    #[arg(long, value_name = "PATH")]
+    pub qr_file: Option<PathBuf>,
+}
+
 #[derive(Debug, Subcommand)]
 pub enum TestCommand {
     /// Get the latest token
-    Token,
+    Token {
+        /// Wait up to this many seconds (at most an hour) for the daemon to
+        /// become ready
+        #[arg(long, value_name = "SECONDS", value_parser = parse_seconds)]
+        wait: Option<Duration>,
+    },
+}
+
+#[derive(Debug, Subcommand)]
+pub enum AuthCommand {
+    /// Authorize a running daemon, or store a new grant with `--output`
+    Login(LoginArgs),
+    /// Print a fresh access token from a stored refresh token
+    Mint {
+        /// Credential file (`-` for standard input), defaults to
+        /// `EBAY_AUTHD_REFRESH_TOKEN`
+        #[arg(long, value_name = "PATH")]
+        refresh_token_file: Option<PathBuf>,
+    },
+    /// Revoke the grant of the running daemon, or of a credential file
+    Logout {
+        /// Revoke and delete this credential file instead
+        #[arg(long, value_name = "PATH")]
+        refresh_token_file: Option<PathBuf>,
+    },
+    /// Show the scopes and expiry of a stored grant
+    Inspect {
+        /// Credential file (`-` for standard input)
+        #[arg(long, value_name = "PATH")]
+        refresh_token_file: PathBuf,
+    },
+}
+
+#[derive(Debug, Args)]
+pub struct LoginArgs {
+    /// Authorize without a daemon and write the grant to this credential file
+    #[arg(long, short, value_name = "PATH")]
+    pub output: Option<PathBuf>,
+    /// Receive the consent redirect on this address instead of asking for it
+    #[arg(long, value_name = "ADDRESS", requires = "output")]
+    pub listen: Option<String>,
+    /// Open the consent URL in the default browser
+    #[arg(long)]
+    pub open: bool,
+    #[command(flatten)]
+    pub qr: QrArgs,
+}
+
+fn parse_seconds(value: &str) -> Result<Duration, ParseIntError> {
+    value.parse().map(Duration::from_secs)
+}
+
+fn parse_size(value: &str) -> Result<u64, String> {
+    let (number, unit) = match value.find(|c: char| !c.is_ascii_digit()) {
+        Some(index) => value.split_at(index),
+        None => (value, ""),
+    };
+
+    let multiplier = match unit.to_ascii_uppercase().as_str() {
+        "" => 1,
+        "K" => 1 << 10,
+        "M" => 1 << 20,
+        "G" => 1 << 30,
+        _ => return Err(format!("unknown size unit `{unit}`")),
+    };
+
+    match number.parse::<u64>().map_err(|e| e.to_string())? {
+        0 => Err("size must be greater than zero".into()),
+        number => number
+            .checked_mul(multiplier)
+            .ok_or_else(|| format!("size `{value}` is too large")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::parse_size;
+
+    #[test]
+    fn parses_sizes() {
+        assert_eq!(parse_size("512"), Ok(512));
+        assert_eq!(parse_size("10k"), Ok(10 << 10));
+        assert_eq!(parse_size("10M"), Ok(10 << 20));
+        assert_eq!(parse_size("1G"), Ok(1 << 30));
+    }
+
+    #[test]
+    fn rejects_invalid_sizes() {
+        for value in [
+            "0",
+            "0M",
+            "99999999999G",
+            "18446744073709551616",
+            "10T",
+            "M",
+            "",
+        ] {
+            assert!(parse_size(value).is_err(), "{value} was accepted");
+        }
+    }
 }
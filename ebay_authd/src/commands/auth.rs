@@ -0,0 +1,197 @@
+use crate::{
+    cli::{LoginArgs, QrArgs},
+    config::configuration::Configuration,
+    consent::{self, Prompt},
+    credentials::{self, Credentials},
+    error::{Error, Result},
+    oauth::{self, CODE_LIFETIME},
+    redirect,
+    tokenmgr::TokenManager,
+};
+use colored::Colorize;
+use ebay_authd_client::{discovery::SocketAddress, Client};
+use ebay_authd_core::{request::Request, response::Response};
+use oauth2::{url::Url, StandardRevocableToken};
+use std::{
+    fs,
+    path::Path,
+    time::{Duration, Instant, SystemTime},
+};
+
+/// Authorize a running daemon from this terminal.
+///
+/// If the daemon is already authorized, it keeps using its current grant
+/// until the new one has been obtained.
+pub fn login(socket: &SocketAddress, open: bool, qr: &QrArgs) -> Result<()> {
+    let mut prompt = Prompt::new();
+
+    'authorization: loop {
+        let response = Client::connect(socket)?.exchange(Request::BeginAuth)?;
+        let deadline = Instant::now() + CODE_LIFETIME;
+
+        let Response::AuthUrl(auth_url) = response else {
+            return Err(Error::UnexpectedResponse);
+        };
+
+        let auth_url = Url::parse(&auth_url)?;
+        consent::show_url(&auth_url, qr)?;
+
+        if open {
+            redirect::open_browser(&auth_url);
+        }
+
+        loop {
+            let Some(input) = prompt.read_until(deadline)? else {
+                eprintln!("{}", Error::AuthorizationExpired.to_string().red());
+                println!("{}", "Starting a new authorization".yellow());
+                continue 'authorization;
+            };
+
+            let response =
+                Client::connect(socket)?.exchange(Request::CompleteAuth(input.into()))?;
+
+            match response {
+                Response::Authorized => break 'authorization,
+                Response::AuthFailed { reason, restart } => {
+                    eprintln!("{} {reason}", "Authorization failed:".red());
+
+                    if restart {
+                        println!("{}", "Starting a new authorization".yellow());
+                        continue 'authorization;
+                    }
+                }
+                Response::Error(why) => return Err(Error::Daemon(why)),
+                _ => return Err(Error::UnexpectedResponse),
+            }
+        }
+    }
+
+    println!("{}", "Daemon authorized".green());
+    Ok(())
+}
+
+/// Authorize without a daemon and store the grant in `output`.
+pub fn login_standalone(config: &Configuration, output: &Path, args: &LoginArgs) -> Result<()> {
+    let client = oauth::client(config)?;
+    let listen = args.listen.as_deref().or(config.listen.as_deref());
+
+    let scopes = config.scopes();
+    let token_result = consent::authorize(&client, &scopes, listen, args.open, &args.qr)?;
+    Credentials::from_token(&token_result, &scopes)?.write(output)?;
+
+    println!("{} {}", "Credentials written to".green(), output.display());
+    Ok(())
+}
+
+/// Print a fresh access token obtained with a stored refresh token.
+pub fn mint(config: &Configuration, refresh_token_file: Option<&Path>) -> Result<()> {
+    let refresh_token = match refresh_token_file {
+        Some(path) => Credentials::read(path)?.refresh_token(),
+        None => credentials::from_env()?.ok_or(Error::NoRefreshToken)?,
+    };
+
+    let tman = TokenManager::from_refresh_token(oauth::client(config)?, refresh_token)?;
+    println!("{}", tman.get_token().expose());
+
+    Ok(())
+}
+
+/// Revoke the grant of a running daemon.
+pub fn logout(socket: &SocketAddress) -> Result<()> {
+    match Client::connect(socket)?.exchange(Request::Revoke)? {
+        Response::Revoked { provider: true } => println!("{}", "Grant revoked".green()),
+        Response::Revoked { provider: false } => {
+            println!("{}", "Daemon was not authorized".yellow());
+        }
+        Response::Error(why) => return Err(Error::Daemon(why)),
+        _ => return Err(Error::UnexpectedResponse),
+    }
+
+    Ok(())
+}
+
+/// Revoke the grant stored in a credential file and delete the file.
+///
+/// The file is kept if revocation fails, so it can be tried again.
+pub fn logout_standalone(config: &Configuration, path: &Path) -> Result<()> {
+    let credentials = Credentials::read(path)?;
+    let client = oauth::client(config)?;
+
+    match oauth::revoke(
+        &client,
+        StandardRevocableToken::RefreshToken(credentials.refresh_token()),
+    ) {
+        Ok(()) => println!("{}", "Grant revoked".green()),
+        Err(why @ Error::NoRevocationUrl) => eprintln!("{}", why.to_string().yellow()),
+        Err(why) => return Err(why),
+    }
+
+    if path != Path::new("-") {
+        fs::remove_file(path)?;
+        println!("Deleted {}", path.display());
+    }
+
+    Ok(())
+}
+
+/// Show what is known about a stored grant.
+pub fn inspect(refresh_token_file: &Path) -> Result<()> {
+    let credentials = Credentials::read(refresh_token_file)?;
+
+    println!(
+        "Credentials: {}",
+        refresh_token_file.display().to_string().blue()
+    );
+
+    if credentials.scopes.is_empty() {
+        println!("Scopes: {}", "Unknown".yellow());
+    } else {
+        println!("Scopes:");
+        for scope in &credentials.scopes {
+            println!("  {}", scope.bright_cyan());
+        }
+    }
+
+    match credentials.obtained {
+        Some(obtained) => println!(
+            "Obtained: {} ({} {})",
+            humantime::format_rfc3339(obtained).to_string().yellow(),
+            format_duration(
+                SystemTime::now()
+                    .duration_since(obtained)
+                    .unwrap_or_default()
+            )
+            .yellow(),
+            "ago".blue()
+        ),
+        None => println!("Obtained: {}", "Unknown".yellow()),
+    }
+
+    match credentials.expires {
+        Some(expires) => match expires.duration_since(SystemTime::now()) {
+            Ok(remaining) => println!(
+                "Refresh token expires: {} ({} {})",
+                humantime::format_rfc3339(expires).to_string().yellow(),
+                "in".blue(),
+                format_duration(remaining).yellow()
+            ),
+            Err(..) => println!(
+                "Refresh token expires: {} ({})",
+                humantime::format_rfc3339(expires).to_string().yellow(),
+                "Expired".red()
+            ),
+        },
+        None => println!("Refresh token expires: {}", "Unknown".yellow()),
+    }
+
+    Ok(())
+}
+
+/// Format `duration` in its largest whole unit of days, hours or minutes.
+fn format_duration(duration: Duration) -> String {
+    match duration.as_secs() {
+        secs @ 86_400.. => format!("{} days", secs / 86_400),
+        secs @ 3_600.. => format!("{} hours", secs / 3_600),
+        secs => format!("{} minutes", secs / 60),
+    }
+}
@@ -0,0 +1,251 @@
+use crate::{
+    config::{
+        self,
+        configuration::{Configuration, Environment},
+        format::Format,
+        migrate::CURRENT_VERSION,
+        overrides, secret,
+        validate::{self, Severity},
+    },
+    error::{Error, Result},
+    oauth::SCOPES,
+};
+use clap::ValueEnum;
+use colored::Colorize;
+use ebay_authd_core::secret::REDACTED;
+use std::{
+    fs,
+    io::{self, BufRead, Write},
+    path::Path,
+};
+
+/// Ask for the configuration fields and write them to `path`.
+pub fn init(path: Option<&Path>, force: bool) -> Result<()> {
+    let path = path.ok_or(Error::NoConfigLocation)?;
+
+    if path.exists() && !force {
+        return Err(Error::ConfigExists(path.into()));
+    }
+
+    println!("Creating {}", path.display());
+    println!("The application keys are listed at https://developer.ebay.com/my/keys\n");
+
+    let environment = ask(
+        "Environment (production, sandbox)",
+        Some("production"),
+        |value| Environment::from_str(value, true).map(|_| ()),
+    )?;
+    let environment = Environment::from_str(&environment, true).unwrap_or_default();
+
+    let appid = ask("App ID (Client ID)", None, |value| {
+        validate::appid(value, environment)
+    })?;
+    let devid = ask("Dev ID", None, validate::devid)?;
+    let certid = ask(
+        "Cert ID (Client Secret), or a reference like env:VAR, file:PATH or cmd:COMMAND",
+        None,
+        |value| {
+            if secret::is_reference(value) {
+                Ok(())
+            } else {
+                validate::certid(value, environment)
+            }
+        },
+    )?;
+    let redirecturi = ask("RuName (eBay Redirect URL name)", None, validate::runame)?;
+    let scopes = ask(
+        "Scopes, separated by commas",
+        Some("all"),
+        |value| match parse_scopes(value) {
+            Some(scopes) => validate::scopes(&scopes),
+            None => Ok(()),
+        },
+    )?;
+
+    let config = Configuration {
+        appid: appid.into(),
+        devid: devid.into(),
+        certid: certid.into(),
+        redirecturi: redirecturi.into(),
+        environment,
+        scopes: parse_scopes(&scopes),
+        ..Configuration::default()
+    };
+
+    config::write(path, &config)?;
+    println!(
+        "\n{} {}",
+        "Configuration written to".green(),
+        path.display()
+    );
+
+    Ok(())
+}
+
+/// Check the configuration and print all problems found.
+pub fn validate(path: Option<&Path>) -> Result<()> {
+    let file = path.filter(|path| path.is_file());
+
+    let mut problems = Vec::new();
+
+    match file {
+        Some(path) => {
+            println!("Checking {}", path.display());
+            problems.extend(validate::check_file(path, &config::read_file(path)?)?);
+        }
+        None if overrides::present() => println!("Checking environment variables"),
+        None => {
+            let location = path.map_or_else(
+                || "any location".to_string(),
+                |path| path.display().to_string(),
+            );
+            eprintln!(
+                "{} No configuration file at {location}, run `ebay_authd config init`",
+                "error:".red()
+            );
+            return Err(Error::InvalidConfig("no configuration".into()));
+        }
+    }
+
+    problems.extend(validate::check(&config::load(path)?));
+
+    for problem in &problems {
+        match problem.severity {
+            Severity::Error => eprintln!("{} {problem}", "error:".red()),
+            Severity::Warning => eprintln!("{} {problem}", "warning:".yellow()),
+        }
+    }
+
+    let errors = problems
+        .iter()
+        .filter(|problem| problem.severity == Severity::Error)
+        .count();
+
+    if errors > 0 {
+        return Err(Error::InvalidConfig(
+            format!("{errors} error(s) found").into(),
+        ));
+    }
+
+    println!("{}", "Configuration is valid".green());
+    Ok(())
+}
+
+/// Print the effective configuration, after environment overrides and with
+/// secrets redacted.
+pub fn show(path: Option<&Path>) -> Result<()> {
+    let mut config = config::load(path)?;
+
+    if !config.certid.is_empty() {
+        config.certid = REDACTED.into();
+    }
+
+    if config.scopes.is_none() {
+        config.scopes = Some(SCOPES.iter().map(|scope| (*scope).into()).collect());
+    }
+
+    let file = path.filter(|path| path.is_file());
+    match file {
+        Some(path) => println!("# {}", path.display()),
+        None => println!("# No configuration file"),
+    }
+
+    let format = file.map_or(Format::Yaml, Format::of);
+    let contents = format
+        .serialize(&config)
+        .map_err(|reason| Error::ConfigSerialize(reason.into()))?;
+    println!("{}", contents.trim_end());
+
+    Ok(())
+}
+
+/// Upgrade the configuration file to the current layout.
+///
+/// Prints the upgraded file, or replaces it if `write` is set. The original
+/// file is kept with a `.v<version>.bak` suffix.
+pub fn migrate(path: Option<&Path>, write: bool) -> Result<()> {
+    let path = path
+        .filter(|path| path.is_file())
+        .ok_or(Error::NoConfigLocation)?;
+    let (config, version) = config::read_versioned(path)?;
+
+    if version == CURRENT_VERSION {
+        println!("{} is already at version {version}", path.display());
+        return Ok(());
+    }
+
+    if !write {
+        let contents = Format::of(path)
+            .serialize(&config)
+            .map_err(|reason| Error::ConfigSerialize(reason.into()))?;
+        println!("# {} upgraded from version {version}", path.display());
+        println!("{}", contents.trim_end());
+
+        return Ok(());
+    }
+
+    let mut backup = path.as_os_str().to_owned();
+    backup.push(format!(".v{version}.bak"));
+    fs::copy(path, &backup)?;
+    config::write(path, &config)?;
+
+    println!(
+        "{} {} from version {version} to {CURRENT_VERSION}, backup in {}",
+        "Upgraded".green(),
+        path.display(),
+        Path::new(&backup).display()
+    );
+
+    Ok(())
+}
+
+/// Ask for a value until `check` accepts it.
+fn ask(
+    question: &str,
+    default: Option<&str>,
+    check: impl Fn(&str) -> std::result::Result<(), String>,
+) -> Result<String> {
+    let stdin = io::stdin();
+
+    loop {
+        match default {
+            Some(default) => print!("{question} [{default}]: "),
+            None => print!("{question}: "),
+        }
+        io::stdout().flush()?;
+
+        let mut line = String::new();
+        if stdin.lock().read_line(&mut line)? == 0 {
+            return Err(Error::InputClosed);
+        }
+
+        let value = match line.trim() {
+            "" => match default {
+                Some(default) => default,
+                None => continue,
+            },
+            value => value,
+        };
+
+        match check(value) {
+            Ok(()) => return Ok(value.to_string()),
+            Err(why) => eprintln!("{} {why}", "Invalid value:".red()),
+        }
+    }
+}
+
+/// Scopes entered by the user, `None` for all scopes.
+fn parse_scopes(value: &str) -> Option<Vec<Box<str>>> {
+    if value.eq_ignore_ascii_case("all") {
+        return None;
+    }
+
+    Some(
+        value
+            .split(',')
+            .map(str::trim)
+            .filter(|scope| !scope.is_empty())
+            .map(Into::into)
+            .collect(),
+    )
+}
@@ -1,153 +1,217 @@
 use crate::{
-    config::configuration::Configuration,
+    cli::{LogArgs, StartArgs},
+    config::{configuration::Configuration, secret},
+    consent,
+    credentials::{self, Credentials},
+    daemonize,
     error::{Error, Result},
+    helper::{Access, Helper},
+    instance::{self, InstanceLock},
+    lifecycle::Daemon,
+    memory,
+    multiplexer::MultiplexerKind,
+    oauth::OAuthClient,
+    reload::Reloader,
+    sandbox::Sandbox,
+    signals,
+    systemd::{self, Notifier},
     tokenmgr::TokenManager,
+    user,
 };
-use ebay_authd_client::Client;
+use ebay_authd_client::{discovery::SocketAddress, Client};
 use ebay_authd_core::{request::Request, response::Response};
-use log::{debug, error, info, warn};
+use log::{debug, error, info, warn, LevelFilter};
 use nix::{
     errno::Errno,
     sys::{
         select::{select, FdSet},
         time::TimeVal,
     },
-};
-use oauth2::{
-    basic::BasicClient, reqwest::http_client, url::Url, AuthUrl, AuthorizationCode, ClientId,
-    ClientSecret, CsrfToken, PkceCodeChallenge, RedirectUrl, Scope, TokenUrl,
+    unistd::User,
 };
 use std::{
     env, fs,
-    io::stdin,
-    os::{
-        fd::{AsFd, AsRawFd, BorrowedFd},
-        unix::net::UnixListener,
-    },
-    path::Path,
-    process::{exit, Command},
-    str::FromStr,
-    sync::atomic::{AtomicBool, Ordering},
-    thread::sleep,
+    os::fd::{AsFd, AsRawFd, BorrowedFd},
+    path::{self, Path},
+    process,
     time::Duration,
 };
 
-pub const SOCKET_PATH: &str = "/tmp/ebay_authd.sock";
-const TOKEN_URL: &str = "https://api.ebay.com/identity/v1/oauth2/token";
-const AUTH_URL: &str = "https://auth.ebay.com/oauth2/authorize";
-const REDIRECT_URL: &str =
-    "https://signin.ebay.com/ws/eBayISAPI.dll?ThirdPartyAuthSucessFailure&isAuthSuccessful=true";
-const SCOPES: [&str; 12] = [
-    "https://api.ebay.com/oauth/api_scope",
-    "https://api.ebay.com/oauth/api_scope/sell.marketing.readonly",
-    "https://api.ebay.com/oauth/api_scope/sell.inventory.readonly",
-    "https://api.ebay.com/oauth/api_scope/sell.account.readonly",
-    "https://api.ebay.com/oauth/api_scope/sell.fulfillment.readonly",
-    "https://api.ebay.com/oauth/api_scope/sell.analytics.readonly",
-    "https://api.ebay.com/oauth/api_scope/sell.finances",
-    "https://api.ebay.com/oauth/api_scope/sell.payment.dispute",
-    "https://api.ebay.com/oauth/api_scope/commerce.identity.readonly",
-    "https://api.ebay.com/oauth/api_scope/sell.reputation.readonly",
-    "https://api.ebay.com/oauth/api_scope/commerce.notification.subscription.readonly",
-    "https://api.ebay.com/oauth/api_scope/sell.stores.readonly",
-];
-
-static STOP: AtomicBool = AtomicBool::new(false);
-
-pub fn start(config: &Configuration, screen: bool) -> Result<()> {
-    if screen {
-        check_screen()?;
-
-        if !running_in_screen() {
-            info!("Restarting using screen");
-            restart_in_screen()?;
+pub fn start(
+    config: Configuration,
+    location: Option<&Path>,
+    socket: &SocketAddress,
+    args: StartArgs,
+    log: &LogArgs,
+) -> Result<()> {
+    memory::harden();
+
+    let multiplexer = args
+        .multiplexer
+        .or(args.screen.then_some(MultiplexerKind::Screen))
+        .or(config.multiplexer.filter(|_| !args.detach))
+        .map(MultiplexerKind::multiplexer);
+
+    if let Some(multiplexer) = &multiplexer {
+        let name = multiplexer.name();
+        multiplexer.check()?;
+
+        if !multiplexer.running_inside() {
+            info!("Restarting using {name}");
+            multiplexer.restart_inside()?;
         }
 
-        info!("Screen session detected");
+        info!("{name} session detected");
     }
 
-    info!("Creating clie7nt");
-    let client = BasicClient::new(
-        ClientId::new(config.appid.to_string()),
-        Some(ClientSecret::new(config.certid.to_string())),
-        AuthUrl::new(AUTH_URL.to_string())?,
-        Some(TokenUrl::new(TOKEN_URL.to_string())?),
-    )
-    .set_redirect_uri(RedirectUrl::new(REDIRECT_URL.to_string())?);
-
-    debug!("Generating PKCE challenge");
-    let (pkce_challenge, pkce_verifier) = PkceCodeChallenge::new_random_sha256();
-
-    info!("Authorizing...");
-    let mut auth_request = client.authorize_url(CsrfToken::new_random);
-    for scope in SCOPES {
-        auth_request = auth_request.add_scope(Scope::new(scope.to_string()));
+    let user = args.user.as_deref().map(user::lookup).transpose()?;
+    let mut lock = InstanceLock::acquire(socket)?;
+
+    let log_file = log
+        .log_file
+        .clone()
+        .or_else(|| args.detach.then(|| instance::log_file(socket)));
+    let detach_log = log_file.as_deref().filter(|_| args.detach);
+
+    let mut sandbox = Sandbox::new(args.sandbox);
+    let files = [
+        Some(instance::pid_file(socket)),
+        socket.path().map(Path::to_path_buf),
+        log_file.clone(),
+    ];
+    for file in files.iter().flatten() {
+        if let Some(parent) = file.parent() {
+            sandbox.allow_write(parent);
+        }
+    }
+    if let Some(parent) = location.and_then(Path::parent) {
+        sandbox.allow_read(parent);
+    }
+    if let Some(credentials) = env::var_os(secret::CREDENTIALS_DIRECTORY_ENV) {
+        sandbox.allow_read(Path::new(&credentials));
     }
-    let (auth_url, _) = auth_request.set_pkce_challenge(pkce_challenge).url();
-
-    info!("Generated auth URL");
-    println!("\nPlease authenticate using the following URL:");
-    println!("{auth_url}\n");
-
-    debug!("Waiting for authentication");
-    println!("Enter the URL after authentication:");
-
-    let mut buffer = String::new();
-    stdin().read_line(&mut buffer)?;
-    let buffer = buffer.trim_end().to_string();
-
-    let url = Url::from_str(&buffer)?;
-    let auth_code = url
-        .query_pairs()
-        .find(|pair| pair.0 == "code")
-        .map(|(_, value)| value)
-        .unwrap();
-
-    let token_result = client
-        .exchange_code(AuthorizationCode::new(auth_code.into()))
-        .set_pkce_verifier(pkce_verifier)
-        .request(http_client)
-        .map_err(|_| Error::TokenRequest)?;
-
-    let tman = TokenManager::new(client, token_result);
 
-    info!("Success, starting daemon");
+    // Absolute, as detaching changes the working directory
+    let credentials = args
+        .refresh_token_file
+        .as_deref()
+        .filter(|path| *path != Path::new("-"))
+        .map(path::absolute)
+        .transpose()?;
+
+    // Only the helper obtains the grant and the Cert ID
+    let (helper, config, access) = Helper::spawn(
+        location,
+        credentials.as_deref(),
+        config,
+        sandbox.clone(),
+        |client, config| obtain_grant(client, config, &args),
+        || match detach_log {
+            Some(log_file) => daemonize::detach_helper(log_file, log.log_max_size, log.log_keep),
+            None => Ok(()),
+        },
+    )?;
+
+    if let Some(multiplexer) = &multiplexer {
+        info!("Detaching {}", multiplexer.name());
+        multiplexer.detach()?;
+    }
 
-    if screen {
-        info!("Detaching screen");
-        detach_screen()?;
+    if let Some(log_file) = detach_log {
+        daemonize::detach(&mut lock, log_file, log.log_max_size, log.log_keep)?;
     }
 
-    daemon_loop(tman)?;
+    daemon_loop(
+        Daemon::new(helper, config.scopes(), access),
+        socket,
+        Reloader::new(location, config),
+        args.revoke_on_stop,
+        sandbox,
+        user,
+    )?;
     info!("Daemon stopped");
 
     Ok(())
 }
 
-#[allow(clippy::needless_pass_by_value)]
-pub fn daemon_loop(mut tman: TokenManager) -> Result<()> {
-    if Path::new(SOCKET_PATH).exists() {
-        warn!("Found existing socket, removing it");
-        fs::remove_file(SOCKET_PATH)?;
+/// Obtain the grant the daemon starts with, in the refresh helper. `None`
+/// when starting without authentication.
+fn obtain_grant(
+    client: &OAuthClient,
+    config: &Configuration,
+    args: &StartArgs,
+) -> Result<Option<TokenManager>> {
+    let refresh_token = match &args.refresh_token_file {
+        Some(path) => Some(Credentials::read(path)?.refresh_token()),
+        None => credentials::from_env()?,
+    };
+
+    if let Some(refresh_token) = refresh_token {
+        let tman = TokenManager::from_refresh_token(client.clone(), refresh_token)?;
+        info!("Refresh token accepted, starting daemon");
+        Ok(Some(tman))
+    } else if args.headless {
+        info!("Starting without authentication, run `ebay_authd auth login` to authorize");
+        Ok(None)
+    } else {
+        let listen = args.listen.as_deref().or(config.listen.as_deref());
+        let token_result =
+            consent::authorize(client, &config.scopes(), listen, args.open, &args.qr)?;
+        let tman = TokenManager::new(client.clone(), token_result)?;
+        info!("Success, starting daemon");
+        Ok(Some(tman))
     }
+}
 
-    debug!("Starting UNIX socket");
-    let listener = UnixListener::bind(SOCKET_PATH)?;
+/// Serve clients until a stop is requested.
+///
+/// The grant is revoked when stopping if `revoke_on_stop` is set, or enabled
+/// in the configuration. Once the socket is set up, the process switches to
+/// `user` and `sandbox` is applied.
+pub fn daemon_loop(
+    mut daemon: Daemon,
+    socket: &SocketAddress,
+    mut reloader: Reloader,
+    revoke_on_stop: bool,
+    sandbox: Sandbox,
+    user: Option<User>,
+) -> Result<()> {
+    let activated = systemd::activated_listener()?;
+    let owns_socket = activated.is_none();
+
+    let listener = match activated {
+        Some(listener) => listener,
+        None => {
+            instance::prepare_socket(socket)?;
+
+            debug!("Starting UNIX socket on {socket}");
+            socket.bind()?
+        }
+    };
     listener.set_nonblocking(true)?;
     let mut clients: Vec<Client> = Vec::new();
 
-    ctrlc::set_handler(|| {
-        STOP.store(true, Ordering::SeqCst);
-        info!("Please wait...");
-    })
-    .unwrap();
+    signals::install()?;
+    if let Some(user) = &user {
+        user::switch(user)?;
+    }
+    sandbox.apply()?;
+
+    let mut notifier = Notifier::new();
+    notifier.started(&daemon);
 
     'outer: loop {
-        if STOP.load(Ordering::Relaxed) {
+        if signals::stop_requested() {
             info!("Got stop signal");
             break;
         }
 
+        if signals::reload_requested() || reloader.changed() {
+            if let Err(why) = reloader.reload(&mut daemon) {
+                error!("Keeping current configuration: {why}");
+            }
+        }
+
         let mut fds = FdSet::new();
         fds.insert(listener.as_fd());
 
@@ -165,12 +229,20 @@ pub fn daemon_loop(mut tman: TokenManager) -> Result<()> {
         );
 
         match select_result {
-            Ok(0) => tman.tick()?,
+            Ok(0) => daemon.tick(),
             Err(Errno::EINTR) => continue,
             Err(other) => return Err(other.into()),
             Ok(..) => (),
         }
 
+        // Stop, also pinging the watchdog, so a supervisor can restart it
+        if daemon.helper_exited() {
+            error!("Refresh helper exited, stopping");
+            break;
+        }
+
+        notifier.tick(&daemon);
+
         for fd in fds.fds(None) {
             if fd.as_raw_fd() == listener.as_raw_fd() {
                 debug!("New client!");
@@ -180,22 +252,17 @@ pub fn daemon_loop(mut tman: TokenManager) -> Result<()> {
 
             debug!("Handling client");
 
-            let (index, client) = clients
-                .iter_mut()
-                .enumerate()
-                .find(|(_, client)| **client == fd)
-                .unwrap();
+            let index = clients.iter().position(|client| *client == fd).unwrap();
+            let mut client = clients.remove(index);
 
             let message = match client.await_message() {
                 Ok(Some(msg)) => msg,
                 Ok(None) => {
                     warn!("Client broken, kicking");
-                    clients.remove(index);
                     continue;
                 }
                 Err(why) => {
                     error!("Failed to parse message: {why}");
-                    clients.remove(index);
                     continue;
                 }
             };
@@ -207,98 +274,131 @@ pub fn daemon_loop(mut tman: TokenManager) -> Result<()> {
 
             debug!("Handling client request");
 
-            if let Err(why) = handle_client(client, request, &mut tman) {
+            if let Err(why) = handle_client(client, request, &mut daemon, &mut reloader) {
                 if matches!(why, Error::StopRequested) {
                     break 'outer;
                 }
 
                 error!("Failed to process request: {why}");
             }
+        }
+    }
 
-            clients.remove(index);
+    let helper_exited = daemon.helper_exited();
+    if !helper_exited && (revoke_on_stop || reloader.config().revoke_on_stop) {
+        match daemon.revoke() {
+            Ok(true) => info!("Grant revoked"),
+            Ok(false) => (),
+            Err(why) => error!("Failed to revoke grant: {why}"),
         }
     }
 
+    daemon.stop();
+    notifier.stopping();
+
     info!("Closing socket");
-    fs::remove_file(SOCKET_PATH)?;
+    if let Some(path) = socket.path().filter(|_| owns_socket) {
+        // May fail after switching users
+        if let Err(why) = fs::remove_file(path) {
+            warn!("Failed to remove socket {}: {why}", path.display());
+        }
+    }
+
+    if helper_exited {
+        return Err(Error::HelperExited);
+    }
 
     Ok(())
 }
 
-fn handle_client(client: &mut Client, request: Request, tman: &mut TokenManager) -> Result<()> {
+fn handle_client(
+    mut client: Client,
+    request: Request,
+    daemon: &mut Daemon,
+    reloader: &mut Reloader,
+) -> Result<()> {
     match request {
-        Request::Token => client.message(Response::Token(tman.get_token().into()))?,
+        Request::Token => daemon.request_token(client, Duration::ZERO)?,
+        Request::AwaitToken(timeout) => daemon.request_token(client, timeout)?,
         Request::Status => {
+            let access = daemon.access();
+
             client.message(Response::Status {
                 version: env!("CARGO_PKG_VERSION").into(),
-                expiry: tman.expiry(),
-                last_refresh: tman.last_refresh(),
-                short_token: tman.short_token(),
-                short_refresh_token: tman.short_refresh_token(),
+                pid: process::id(),
+                state: daemon.state(),
+                expiry: access.map(Access::expiry),
+                last_refresh: access.map(Access::last_refresh),
+                token_fingerprint: access.map(Access::token_fingerprint),
+                refresh_token_fingerprint: access.map(Access::refresh_token_fingerprint),
             })?;
         }
-        Request::ForceRefresh => {
-            tman.refresh()?;
-        }
+        Request::ForceRefresh => daemon.refresh()?,
         Request::Stop => {
             info!("Stop requested");
             return Err(Error::StopRequested);
         }
-    };
-
-    Ok(())
-}
-
-fn check_screen() -> Result<()> {
-    match Command::new("screen").arg("--version").output() {
-        Ok(output) if output.status.success() => {
-            let version_string = String::from_utf8(output.stdout)?;
-            debug!("Found screen: {version_string}");
-
-            Ok(())
-        }
-        Ok(output) if !output.status.success() => {
-            error!(
-                "screen found but got unexpected status code: {}",
-                output.status
-            );
-            Err(Error::Screen)
-        }
-        Ok(..) => unreachable!(),
-        Err(why) => {
-            error!("screen not installed or not in PATH: {why}");
-            Err(Error::Screen)
+        Request::BeginAuth => {
+            info!("Authorization requested by client");
+            match daemon.begin_auth() {
+                Ok(auth_url) => client.message(Response::AuthUrl(auth_url))?,
+                Err(why) => {
+                    client.message(Response::Error(why.to_string().into()))?;
+                    return Err(why);
+                }
+            }
         }
-    }
-}
-
-fn restart_in_screen() -> Result<()> {
-    let args: Vec<String> = env::args().collect();
-
-    // Create a new session
-    Command::new("screen")
-        .arg("-dmS")
-        .arg("ebay_authd")
-        .args(args)
-        .output()?;
-
-    sleep(Duration::from_secs(2));
-
-    // Attach to the new session
-    Command::new("screen")
-        .arg("-r")
-        .arg("ebay_authd")
-        .spawn()?
-        .wait()?;
-
-    exit(0);
-}
-
-fn running_in_screen() -> bool {
-    env::var("STY").is_ok_and(|value| !value.is_empty())
-}
+        Request::CompleteAuth(input) => match daemon.complete_auth(input) {
+            Ok(()) => {
+                info!("Authorization completed by client");
+                client.message(Response::Authorized)?;
+            }
+            Err(why) => {
+                client.message(Response::AuthFailed {
+                    reason: why.to_string().into(),
+                    restart: !daemon.auth_pending(),
+                })?;
+                return Err(why);
+            }
+        },
+        Request::Reload => match reloader.reload(daemon) {
+            Ok(restart_required) => client.message(Response::Reloaded {
+                restart_required: restart_required.into_iter().map(Into::into).collect(),
+            })?,
+            Err(why) => {
+                client.message(Response::Error(
+                    format!("Keeping current configuration: {why}").into(),
+                ))?;
+                return Err(why);
+            }
+        },
+        Request::SetLogLevel(level) => match level.parse::<LevelFilter>() {
+            Ok(level) => {
+                if let Err(why) = daemon.set_log_level(level) {
+                    client.message(Response::Error(
+                        format!("Failed to set the log level of the refresh helper: {why}").into(),
+                    ))?;
+                    return Err(why);
+                }
+                client.message(Response::LogLevel(level.as_str().into()))?;
+            }
+            Err(_) => client.message(Response::Error(
+                format!("Unknown log level `{level}`").into(),
+            ))?,
+        },
+        Request::Revoke => match daemon.revoke() {
+            Ok(provider) => {
+                info!("Grant revoked by client");
+                client.message(Response::Revoked { provider })?;
+            }
+            Err(why) => {
+                client.message(Response::Error(
+                    format!("Tokens forgotten, but not revoked at eBay: {why}").into(),
+                ))?;
+                return Err(why);
+            }
+        },
+    };
 
-fn detach_screen() -> Result<()> {
-    Command::new("screen").arg("-d").arg("ebay_authd").spawn()?;
     Ok(())
 }
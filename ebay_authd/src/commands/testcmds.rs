@@ -1,24 +1,27 @@
-use super::daemon::SOCKET_PATH;
 use crate::error::{Error, Result};
 use colored::Colorize;
-use ebay_authd_client::Client;
-use ebay_authd_core::{request::Request, response::Response};
-use std::os::unix::net::UnixStream;
+use ebay_authd_client::{discovery::SocketAddress, Client};
+use ebay_authd_core::{request::Request, response::Response, state::State};
+use log::LevelFilter;
+use std::time::Duration;
 
-pub fn token() -> Result<()> {
-    let mut client = connect()?;
-    let response = client.exchange(Request::Token)?;
+pub fn token(socket: &SocketAddress, wait: Option<Duration>) -> Result<()> {
+    let mut client = Client::connect(socket)?;
+    let request = wait.map_or(Request::Token, Request::AwaitToken);
+    let response = client.exchange(request)?;
 
-    let Response::Token(token) = response else {
-        return Err(Error::UnexpectedResponse);
-    };
+    match response {
+        Response::Token(token) => println!("{}", token.expose()),
+        Response::NotReady(state) => return Err(Error::NotReady(state)),
+        Response::Error(why) => return Err(Error::Daemon(why)),
+        _ => return Err(Error::UnexpectedResponse),
+    }
 
-    println!("{token}");
     Ok(())
 }
 
-pub fn status() {
-    let mut client = match connect() {
+pub fn status(socket: &SocketAddress) {
+    let mut client = match Client::connect(socket) {
         Ok(client) => client,
         Err(why) => {
             eprintln!("{} {why}", "Failed to connect to daemon:".red());
@@ -36,10 +39,12 @@ pub fn status() {
 
     let Response::Status {
         version,
+        pid,
+        state,
         expiry,
         last_refresh,
-        short_token,
-        short_refresh_token,
+        token_fingerprint,
+        refresh_token_fingerprint,
     } = response
     else {
         eprintln!("{} {response:?}", "Daemon sent wrong response:".red());
@@ -48,6 +53,32 @@ pub fn status() {
 
     println!("Daemon: {}", "Running".green());
     println!("Version: {}", version.blue());
+    println!("PID: {}", pid.to_string().blue());
+
+    let state_string = state.to_string();
+    let state_string = match state {
+        State::Ready => state_string.green(),
+        State::Authorizing | State::Refreshing | State::Stopping => state_string.yellow(),
+        State::Unauthenticated | State::Degraded | State::ReauthRequired => state_string.red(),
+    };
+    println!("State: {state_string}");
+
+    let (
+        Some(expiry),
+        Some(last_refresh),
+        Some(token_fingerprint),
+        Some(refresh_token_fingerprint),
+    ) = (
+        expiry,
+        last_refresh,
+        token_fingerprint,
+        refresh_token_fingerprint,
+    )
+    else {
+        println!("Token: {}", "Not authenticated".red());
+        return;
+    };
+
     println!(
         "Token expiry: {}{}",
         expiry.as_secs().to_string().yellow(),
@@ -60,31 +91,47 @@ pub fn status() {
         "ago".blue()
     );
 
-    println!(
-        "Current token: {}{}",
-        short_token.bright_cyan(),
-        "...".bright_red()
-    );
-    println!(
-        "Refresh token: {}{}",
-        short_refresh_token.bright_cyan(),
-        "...".bright_red()
-    );
+    println!("Current token: {}", token_fingerprint.bright_cyan());
+    println!("Refresh token: {}", refresh_token_fingerprint.bright_cyan());
 }
 
-pub fn reauth() -> Result<()> {
-    connect()?.exchange(Request::ForceRefresh)?;
+pub fn refresh(socket: &SocketAddress) -> Result<()> {
+    Client::connect(socket)?.message(Request::ForceRefresh)?;
     Ok(())
 }
 
-pub fn stop() -> Result<()> {
-    connect()?.message(Request::Stop)?;
+pub fn stop(socket: &SocketAddress) -> Result<()> {
+    Client::connect(socket)?.message(Request::Stop)?;
     Ok(())
 }
 
-fn connect() -> Result<Client> {
-    let client = UnixStream::connect(SOCKET_PATH)?;
-    let client = Client::new(client)?;
+pub fn reload(socket: &SocketAddress) -> Result<()> {
+    match Client::connect(socket)?.exchange(Request::Reload)? {
+        Response::Reloaded { restart_required } => {
+            println!("{}", "Configuration reloaded".green());
 
-    Ok(client)
+            for field in restart_required {
+                println!(
+                    "{} `{field}` takes effect after a restart",
+                    "Note:".yellow()
+                );
+            }
+        }
+        Response::Error(why) => return Err(Error::Daemon(why)),
+        _ => return Err(Error::UnexpectedResponse),
+    }
+
+    Ok(())
+}
+
+pub fn log_level(socket: &SocketAddress, level: LevelFilter) -> Result<()> {
+    let request = Request::SetLogLevel(level.as_str().into());
+
+    match Client::connect(socket)?.exchange(request)? {
+        Response::LogLevel(level) => println!("Log level: {}", level.blue()),
+        Response::Error(why) => return Err(Error::Daemon(why)),
+        _ => return Err(Error::UnexpectedResponse),
+    }
+
+    Ok(())
 }
@@ -1,20 +1,104 @@
+use super::migrate::CURRENT_VERSION;
+use crate::{multiplexer::MultiplexerKind, oauth::SCOPES};
+use clap::ValueEnum;
+use ebay_authd_core::secret::Secret;
+use log::LevelFilter;
 use serde::{Deserialize, Serialize};
+use std::fmt;
 
 #[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
 pub struct Configuration {
+    /// Layout version of the file, see `config::migrate`
+    #[serde(default)]
+    pub version: u32,
     pub appid: Box<str>,
     pub devid: Box<str>,
-    pub certid: Box<str>,
+    pub certid: Secret,
     pub redirecturi: Box<str>,
+    /// eBay environment the application keys belong to
+    #[serde(default)]
+    pub environment: Environment,
+    /// OAuth scopes to request, all supported scopes if not set
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub scopes: Option<Vec<Box<str>>>,
+    /// Socket path, or `@name` for a Linux abstract socket
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub socket: Option<Box<str>>,
+    /// Terminal multiplexer used by `daemon start` unless `--detach` is given
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub multiplexer: Option<MultiplexerKind>,
+    /// Address to receive the consent redirect on, e.g. `127.0.0.1:8080`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub listen: Option<Box<str>>,
+    /// OAuth token revocation endpoint used by `auth logout`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub revocation_url: Option<Box<str>>,
+    /// Revoke the grant when the daemon stops
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub revoke_on_stop: bool,
+    /// Log level unless `--log-level` is given
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub log_level: Option<LevelFilter>,
 }
 
 impl Default for Configuration {
     fn default() -> Self {
         Self {
+            version: CURRENT_VERSION,
             appid: "".into(),
             devid: "".into(),
-            certid: "".into(),
+            certid: Secret::default(),
             redirecturi: "".into(),
+            environment: Environment::default(),
+            scopes: None,
+            socket: None,
+            multiplexer: None,
+            listen: None,
+            revocation_url: None,
+            revoke_on_stop: false,
+            log_level: None,
+        }
+    }
+}
+
+impl Configuration {
+    /// Scopes requested during authorization.
+    #[must_use]
+    pub fn scopes(&self) -> Box<[Box<str>]> {
+        match &self.scopes {
+            Some(scopes) => scopes.iter().cloned().collect(),
+            None => SCOPES.iter().map(|scope| (*scope).into()).collect(),
+        }
+    }
+}
+
+/// eBay environment, production or the sandbox.
+#[derive(
+    Debug,
+    Default,
+    Clone,
+    Copy,
+    PartialEq,
+    Eq,
+    PartialOrd,
+    Ord,
+    Hash,
+    Serialize,
+    Deserialize,
+    ValueEnum,
+)]
+#[serde(rename_all = "lowercase")]
+pub enum Environment {
+    #[default]
+    Production,
+    Sandbox,
+}
+
+impl fmt::Display for Environment {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Production => write!(f, "production"),
+            Self::Sandbox => write!(f, "sandbox"),
         }
     }
 }
@@ -0,0 +1,38 @@
+use serde::{de::DeserializeOwned, Serialize};
+use std::path::Path;
+
+/// File format of the configuration, chosen by file extension.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    Yaml,
+    Toml,
+    Json,
+}
+
+impl Format {
+    /// Format of the file at `path`, YAML unless the extension says otherwise.
+    #[must_use]
+    pub fn of(path: &Path) -> Self {
+        match path.extension().and_then(|extension| extension.to_str()) {
+            Some("toml") => Self::Toml,
+            Some("json") => Self::Json,
+            _ => Self::Yaml,
+        }
+    }
+
+    pub fn parse<T: DeserializeOwned>(self, contents: &str) -> Result<T, String> {
+        match self {
+            Self::Yaml => serde_yaml::from_str(contents).map_err(|why| why.to_string()),
+            Self::Toml => toml::from_str(contents).map_err(|why| why.to_string()),
+            Self::Json => serde_json::from_str(contents).map_err(|why| why.to_string()),
+        }
+    }
+
+    pub fn serialize<T: Serialize>(self, value: &T) -> Result<String, String> {
+        match self {
+            Self::Yaml => serde_yaml::to_string(value).map_err(|why| why.to_string()),
+            Self::Toml => toml::to_string(value).map_err(|why| why.to_string()),
+            Self::Json => serde_json::to_string_pretty(value).map_err(|why| why.to_string()),
+        }
+    }
+}
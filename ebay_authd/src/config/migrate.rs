@@ -0,0 +1,102 @@
+use crate::error::{Error, Result};
+use log::debug;
+use serde_json::{Map, Value};
+
+/// Version of the configuration layout written by this release.
+pub const CURRENT_VERSION: u32 = 1;
+
+/// Upgrades from each version to the next, the first entry upgrades
+/// version 0.
+const MIGRATIONS: [fn(&mut Map<String, Value>); CURRENT_VERSION as usize] = [v0_to_v1];
+
+/// Upgrade a parsed configuration file to [`CURRENT_VERSION`] in place.
+///
+/// Returns the version the file was written with. Files without a `version`
+/// field are version 0.
+pub fn migrate(value: &mut Value) -> Result<u32> {
+    let Value::Object(fields) = value else {
+        return Err(Error::ConfigVersion(
+            "the configuration is not a map of fields".into(),
+        ));
+    };
+
+    let version = match fields.get("version") {
+        None => 0,
+        Some(version) => version
+            .as_u64()
+            .and_then(|version| u32::try_from(version).ok())
+            .ok_or_else(|| Error::ConfigVersion(format!("invalid version {version}").into()))?,
+    };
+
+    if version > CURRENT_VERSION {
+        return Err(Error::ConfigVersion(
+            format!(
+                "version {version} was written by a newer release, this one supports up to version {CURRENT_VERSION}"
+            )
+            .into(),
+        ));
+    }
+
+    for (from, migration) in MIGRATIONS.iter().enumerate().skip(version as usize) {
+        debug!(
+            "Migrating configuration from version {from} to {}",
+            from + 1
+        );
+        migration(fields);
+    }
+
+    fields.insert("version".into(), CURRENT_VERSION.into());
+    Ok(version)
+}
+
+/// Unversioned files only lack the `version` field.
+fn v0_to_v1(_fields: &mut Map<String, Value>) {}
+
+#[cfg(test)]
+mod tests {
+    use super::{migrate, CURRENT_VERSION};
+    use crate::error::Error;
+    use serde_json::json;
+
+    #[test]
+    fn upgrades_unversioned_file() {
+        let mut value = json!({ "appid": "Example-App-PRD-0123456789" });
+
+        assert_eq!(migrate(&mut value).unwrap(), 0);
+        assert_eq!(value["version"], CURRENT_VERSION);
+        assert_eq!(value["appid"], "Example-App-PRD-0123456789");
+    }
+
+    #[test]
+    fn keeps_current_file() {
+        let mut value = json!({ "version": CURRENT_VERSION, "appid": "App" });
+
+        assert_eq!(migrate(&mut value).unwrap(), CURRENT_VERSION);
+        assert_eq!(value, json!({ "version": CURRENT_VERSION, "appid": "App" }));
+    }
+
+    #[test]
+    fn rejects_future_version() {
+        let mut value = json!({ "version": CURRENT_VERSION + 1 });
+
+        assert!(matches!(migrate(&mut value), Err(Error::ConfigVersion(_))));
+        assert_eq!(value["version"], CURRENT_VERSION + 1);
+    }
+
+    #[test]
+    fn rejects_invalid_version() {
+        for version in [json!(-1), json!("1"), json!(u64::MAX)] {
+            let mut value = json!({ "version": version });
+
+            assert!(matches!(migrate(&mut value), Err(Error::ConfigVersion(_))));
+        }
+    }
+
+    #[test]
+    fn rejects_non_map() {
+        assert!(matches!(
+            migrate(&mut json!(["appid"])),
+            Err(Error::ConfigVersion(_))
+        ));
+    }
+}
@@ -1,22 +1,137 @@
-use crate::error::{Error, Result};
+use crate::{
+    error::{Error, Result},
+    logger,
+};
 use configuration::Configuration;
-use std::path::PathBuf;
+use ebay_authd_client::discovery::{self, SocketAddress};
+use format::Format;
+use log::{debug, info};
+use std::{
+    fs::{self, DirBuilder, OpenOptions, Permissions},
+    io::Write,
+    os::unix::fs::{DirBuilderExt, OpenOptionsExt, PermissionsExt},
+    path::{Path, PathBuf},
+};
 
 pub mod configuration;
+pub mod format;
+pub mod migrate;
+pub mod overrides;
+pub mod secret;
+pub mod validate;
 
-pub fn location() -> Result<PathBuf> {
-    let mut home_dir = homedir::my_home().ok().flatten().ok_or(Error::NoHome)?;
+/// Location of the configuration file, preferring `cli` (the `--config` flag
+/// or `EBAY_AUTHD_CONFIG`). `None` if there is no home directory.
+#[must_use]
+pub fn location(cli: Option<&Path>) -> Option<PathBuf> {
+    cli.map(Path::to_path_buf)
+        .or_else(discovery::config_location)
+}
+
+/// Write `config` to `path` in the format matching its extension.
+///
+/// The file is only readable by the current user, as it usually contains
+/// the client secret.
+pub fn write(path: &Path, config: &Configuration) -> Result<()> {
+    let contents = Format::of(path)
+        .serialize(config)
+        .map_err(|reason| Error::ConfigSerialize(reason.into()))?;
+
+    if let Some(parent) = path
+        .parent()
+        .filter(|parent| !parent.as_os_str().is_empty() && !parent.exists())
+    {
+        DirBuilder::new()
+            .recursive(true)
+            .mode(0o700)
+            .create(parent)?;
+    }
+
+    let mut file = OpenOptions::new()
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .mode(0o600)
+        .open(path)?;
+    file.set_permissions(Permissions::from_mode(0o600))?;
+    file.write_all(contents.as_bytes())?;
+
+    Ok(())
+}
+
+/// Parse the configuration file at `path` as it is, without environment
+/// overrides or resolving secret references.
+///
+/// Files written for an older layout are upgraded in memory.
+pub fn read_file(path: &Path) -> Result<Configuration> {
+    read_versioned(path).map(|(config, _)| config)
+}
+
+/// Like [`read_file`], also returning the version the file was written with.
+pub fn read_versioned(path: &Path) -> Result<(Configuration, u32)> {
+    debug!("Loading configuration from {}", path.display());
 
-    home_dir.push(".config/");
-    home_dir.push("ebay_authd.yml");
+    let parse_error = |reason: String| Error::ConfigParse {
+        path: path.into(),
+        reason: reason.into(),
+    };
 
-    Ok(home_dir)
+    let mut value: serde_json::Value = Format::of(path)
+        .parse(&fs::read_to_string(path)?)
+        .map_err(parse_error)?;
+
+    let version = migrate::migrate(&mut value)?;
+    if version < migrate::CURRENT_VERSION {
+        info!(
+            "Configuration uses version {version}, run `ebay_authd config migrate --write` to upgrade it"
+        );
+    }
+
+    let config = serde_json::from_value(value).map_err(|why| parse_error(why.to_string()))?;
+    Ok((config, version))
 }
 
-pub fn config_exists() -> Result<bool> {
-    location().map(|path| path.is_file())
+/// Load the configuration, leaving references in secret fields unresolved.
+///
+/// The file at `path` is optional, fields can also be set through
+/// `EBAY_AUTHD_*` environment variables, which take precedence. Commands that
+/// only talk to the daemon need nothing else, so they neither run `cmd:`
+/// references nor need systemd credentials.
+pub fn load_unresolved(path: Option<&Path>) -> Result<Configuration> {
+    let mut config = match path.filter(|path| path.is_file()) {
+        Some(path) => read_file(path)?,
+        None => {
+            debug!("No configuration file, using defaults");
+            Configuration::default()
+        }
+    };
+
+    overrides::apply(&mut config)?;
+
+    Ok(config)
+}
+
+/// Load the configuration like [`load_unresolved`] and resolve the
+/// references in secret fields.
+pub fn load(path: Option<&Path>) -> Result<Configuration> {
+    resolve_secrets(load_unresolved(path)?)
+}
+
+/// Resolve the references in the secret fields of `config`.
+pub fn resolve_secrets(mut config: Configuration) -> Result<Configuration> {
+    config.appid = secret::resolve("appid", &config.appid)?;
+    config.devid = secret::resolve("devid", &config.devid)?;
+    config.certid = secret::resolve("certid", config.certid.expose())?.into();
+    logger::register_secret(config.certid.expose());
+
+    Ok(config)
 }
 
-pub fn create_config() -> Result<()> {
-    Ok(confy::store_path(location()?, Configuration::default())?)
+/// Resolve the socket address, preferring `cli` (the `--socket` flag or
+/// `EBAY_AUTHD_SOCKET`) over the configuration file.
+pub fn socket_address(config: &Configuration, cli: Option<&str>) -> Result<SocketAddress> {
+    match cli.or(config.socket.as_deref()) {
+        Some(value) => Ok(value.parse()?),
+        None => Ok(discovery::default_socket()),
+    }
 }
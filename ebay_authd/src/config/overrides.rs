@@ -0,0 +1,111 @@
+use super::configuration::{Configuration, Environment};
+use crate::{
+    error::{Error, Result},
+    multiplexer::MultiplexerKind,
+};
+use clap::ValueEnum;
+use std::env;
+
+/// Prefix of the environment variables overriding configuration fields.
+pub const PREFIX: &str = "EBAY_AUTHD_";
+
+/// Configuration fields that can be overridden, in upper case.
+const FIELDS: [&str; 12] = [
+    "APPID",
+    "DEVID",
+    "CERTID",
+    "REDIRECTURI",
+    "ENVIRONMENT",
+    "SCOPES",
+    "SOCKET",
+    "MULTIPLEXER",
+    "LISTEN",
+    "REVOCATION_URL",
+    "REVOKE_ON_STOP",
+    "LOG_LEVEL",
+];
+
+/// Whether any configuration field is set in the environment.
+#[must_use]
+pub fn present() -> bool {
+    FIELDS.iter().any(|field| var(field).is_some())
+}
+
+/// Override configuration fields from `EBAY_AUTHD_<FIELD>` environment
+/// variables, e.g. `EBAY_AUTHD_CERTID` or `EBAY_AUTHD_REVOKE_ON_STOP`.
+pub fn apply(config: &mut Configuration) -> Result<()> {
+    if let Some(value) = var("APPID") {
+        config.appid = value.into();
+    }
+    if let Some(value) = var("DEVID") {
+        config.devid = value.into();
+    }
+    if let Some(value) = var("CERTID") {
+        config.certid = value.into();
+    }
+    if let Some(value) = var("REDIRECTURI") {
+        config.redirecturi = value.into();
+    }
+    if let Some(value) = var("ENVIRONMENT") {
+        config.environment = Environment::from_str(&value, true)
+            .map_err(|reason| invalid("ENVIRONMENT", &reason))?;
+    }
+    if let Some(value) = var("SCOPES") {
+        // Separated by whitespace or commas
+        config.scopes = Some(
+            value
+                .split(|c: char| c.is_whitespace() || c == ',')
+                .filter(|scope| !scope.is_empty())
+                .map(Into::into)
+                .collect(),
+        );
+    }
+    if let Some(value) = var("SOCKET") {
+        config.socket = Some(value.into());
+    }
+    if let Some(value) = var("MULTIPLEXER") {
+        config.multiplexer = Some(
+            MultiplexerKind::from_str(&value, true)
+                .map_err(|reason| invalid("MULTIPLEXER", &reason))?,
+        );
+    }
+    if let Some(value) = var("LISTEN") {
+        config.listen = Some(value.into());
+    }
+    if let Some(value) = var("REVOCATION_URL") {
+        config.revocation_url = Some(value.into());
+    }
+    if let Some(value) = var("REVOKE_ON_STOP") {
+        config.revoke_on_stop = parse_bool(&value)
+            .ok_or_else(|| invalid("REVOKE_ON_STOP", "expected true or false"))?;
+    }
+    if let Some(value) = var("LOG_LEVEL") {
+        config.log_level = Some(value.parse().map_err(|_| {
+            invalid(
+                "LOG_LEVEL",
+                "expected off, error, warn, info, debug or trace",
+            )
+        })?);
+    }
+
+    Ok(())
+}
+
+fn var(field: &str) -> Option<String> {
+    env::var(format!("{PREFIX}{field}")).ok()
+}
+
+fn invalid(field: &str, reason: &str) -> Error {
+    Error::EnvOverride {
+        var: format!("{PREFIX}{field}").into(),
+        reason: reason.into(),
+    }
+}
+
+fn parse_bool(value: &str) -> Option<bool> {
+    match value.to_ascii_lowercase().as_str() {
+        "1" | "true" | "yes" | "on" => Some(true),
+        "0" | "false" | "no" | "off" | "" => Some(false),
+        _ => None,
+    }
+}
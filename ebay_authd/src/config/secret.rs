@@ -0,0 +1,80 @@
+use crate::error::{Error, Result};
+use log::debug;
+use std::{
+    env, fs,
+    path::PathBuf,
+    process::{Command, Stdio},
+};
+
+/// Directory systemd passes credentials in (`LoadCredential=`).
+pub const CREDENTIALS_DIRECTORY_ENV: &str = "CREDENTIALS_DIRECTORY";
+
+/// Kinds of secret references.
+const KINDS: [&str; 4] = ["env", "file", "cmd", "credential"];
+
+/// Whether `value` is a reference instead of a literal secret.
+#[must_use]
+pub fn is_reference(value: &str) -> bool {
+    value
+        .split_once(':')
+        .is_some_and(|(kind, _)| KINDS.contains(&kind))
+}
+
+/// Resolve a secret field of the configuration.
+///
+/// Besides a literal value, a field can reference where the secret is kept:
+/// - `env:VAR` reads the environment variable `VAR`
+/// - `file:/path` reads the file at `/path`
+/// - `cmd:command` runs `command` with `sh -c` and uses its output
+/// - `credential:name` reads the systemd credential `name`
+///
+/// Errors name the field and reference, never the secret itself.
+pub fn resolve(field: &'static str, value: &str) -> Result<Box<str>> {
+    let Some((kind, reference)) = value.split_once(':').filter(|_| is_reference(value)) else {
+        return Ok(value.into());
+    };
+
+    let error = |reason: String| Error::Secret {
+        field,
+        reference: value.into(),
+        reason: reason.into(),
+    };
+
+    let secret = match kind {
+        "env" => env::var(reference).map_err(|why| error(why.to_string()))?,
+        "file" => fs::read_to_string(reference).map_err(|why| error(why.to_string()))?,
+        "cmd" => run(reference).map_err(error)?,
+        "credential" => {
+            let directory = env::var_os(CREDENTIALS_DIRECTORY_ENV)
+                .ok_or_else(|| error(format!("{CREDENTIALS_DIRECTORY_ENV} is not set")))?;
+            let path = PathBuf::from(directory).join(reference);
+
+            fs::read_to_string(path).map_err(|why| error(why.to_string()))?
+        }
+        _ => unreachable!("checked by is_reference"),
+    };
+
+    debug!("Resolved {field} from {kind}:{reference}");
+    let secret = secret.trim();
+
+    if secret.is_empty() {
+        return Err(error("value is empty".to_string()));
+    }
+
+    Ok(secret.into())
+}
+
+fn run(command: &str) -> std::result::Result<String, String> {
+    let output = Command::new("sh")
+        .args(["-c", command])
+        .stdin(Stdio::null())
+        .stderr(Stdio::inherit())
+        .output()
+        .map_err(|why| why.to_string())?;
+
+    if !output.status.success() {
+        return Err(format!("command failed ({})", output.status));
+    }
+
+    String::from_utf8(output.stdout).map_err(|_| "output is not valid UTF-8".to_string())
+}
@@ -0,0 +1,263 @@
+use super::{configuration::Configuration, configuration::Environment, secret};
+use crate::error::{Error, Result};
+use ebay_authd_client::discovery::SocketAddress;
+use oauth2::url::Url;
+use std::{fmt, fs, os::unix::fs::PermissionsExt, path::Path};
+
+/// Prefix all eBay OAuth scopes share.
+const SCOPE_PREFIX: &str = "https://api.ebay.com/oauth/api_scope";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    Warning,
+    Error,
+}
+
+/// A problem found in the configuration.
+#[derive(Debug, Clone)]
+pub struct Problem {
+    pub severity: Severity,
+    pub field: &'static str,
+    pub message: Box<str>,
+}
+
+impl fmt::Display for Problem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.field, self.message)
+    }
+}
+
+/// Check the fields of the effective configuration.
+#[must_use]
+pub fn check(config: &Configuration) -> Vec<Problem> {
+    let mut problems = Vec::new();
+    let mut report = |severity, field, result: std::result::Result<(), String>| {
+        if let Err(message) = result {
+            problems.push(Problem {
+                severity,
+                field,
+                message: message.into(),
+            });
+        }
+    };
+    let mut error = |field, result| report(Severity::Error, field, result);
+
+    error("appid", appid(&config.appid, config.environment));
+    error("certid", certid(config.certid.expose(), config.environment));
+
+    if let Some(scopes) = &config.scopes {
+        error("scopes", self::scopes(scopes));
+    }
+
+    if let Some(value) = &config.socket {
+        error("socket", socket(value));
+    }
+
+    if let Some(value) = &config.listen {
+        error("listen", listen(value));
+    }
+
+    if let Some(value) = &config.revocation_url {
+        error("revocation_url", revocation_url(value));
+    }
+
+    // Not used by the OAuth client, only kept for other eBay tooling
+    report(Severity::Warning, "devid", devid(&config.devid));
+    report(
+        Severity::Warning,
+        "redirecturi",
+        runame(&config.redirecturi),
+    );
+
+    problems
+}
+
+/// Check the permissions of the configuration file at `path`.
+///
+/// `raw` is the file's contents before secret references were resolved. A
+/// readable file is only an error if it contains the client secret itself.
+pub fn check_file(path: &Path, raw: &Configuration) -> Result<Vec<Problem>> {
+    let mode = fs::metadata(path)?.permissions().mode();
+
+    if mode & 0o077 == 0 {
+        return Ok(Vec::new());
+    }
+
+    let literal_secret = !raw.certid.is_empty() && !secret::is_reference(raw.certid.expose());
+    let severity = if literal_secret {
+        Severity::Error
+    } else {
+        Severity::Warning
+    };
+
+    Ok(vec![Problem {
+        severity,
+        field: "file",
+        message: format!(
+            "{} is accessible by other users (mode {:o}), run `chmod 600` on it",
+            path.display(),
+            mode & 0o777
+        )
+        .into(),
+    }])
+}
+
+/// Fail unless the application keys the OAuth client uses are valid.
+pub fn require_credentials(config: &Configuration) -> Result<()> {
+    let invalid =
+        |field, message: String| Error::InvalidConfig(format!("{field}: {message}").into());
+
+    appid(&config.appid, config.environment).map_err(|message| invalid("appid", message))?;
+    certid(config.certid.expose(), config.environment).map_err(|message| invalid("certid", message))
+}
+
+/// Fail with the first error in the configuration.
+pub fn require_valid(config: &Configuration) -> Result<()> {
+    match check(config)
+        .into_iter()
+        .find(|problem| problem.severity == Severity::Error)
+    {
+        Some(problem) => Err(Error::InvalidConfig(problem.to_string().into())),
+        None => Ok(()),
+    }
+}
+
+pub fn appid(value: &str, environment: Environment) -> std::result::Result<(), String> {
+    not_empty(value)?;
+    keys_environment(value, "-PRD-", "-SBX-", environment)
+}
+
+pub fn devid(value: &str) -> std::result::Result<(), String> {
+    not_empty(value)?;
+
+    let groups: Vec<&str> = value.split('-').collect();
+    let uuid = groups.iter().map(|group| group.len()).eq([8, 4, 4, 4, 12])
+        && groups
+            .iter()
+            .all(|group| group.chars().all(|c| c.is_ascii_hexdigit()));
+
+    if uuid {
+        Ok(())
+    } else {
+        Err("expected a UUID like 01234567-89ab-cdef-0123-456789abcdef".into())
+    }
+}
+
+pub fn certid(value: &str, environment: Environment) -> std::result::Result<(), String> {
+    not_empty(value)?;
+    keys_environment(value, "PRD-", "SBX-", environment)
+}
+
+pub fn runame(value: &str) -> std::result::Result<(), String> {
+    not_empty(value)?;
+
+    if value.contains("://") {
+        return Err("expected the RuName of the redirect, not a URL".into());
+    }
+
+    Ok(())
+}
+
+pub fn scopes(values: &[Box<str>]) -> std::result::Result<(), String> {
+    if values.is_empty() {
+        return Err("at least one scope is required".into());
+    }
+
+    match values.iter().find(|scope| !scope.starts_with(SCOPE_PREFIX)) {
+        Some(scope) => Err(format!("{scope} is not an eBay OAuth scope")),
+        None => Ok(()),
+    }
+}
+
+fn socket(value: &str) -> std::result::Result<(), String> {
+    value
+        .parse::<SocketAddress>()
+        .map(|_| ())
+        .map_err(|why| why.to_string())
+}
+
+fn listen(value: &str) -> std::result::Result<(), String> {
+    match value.rsplit_once(':') {
+        Some((host, port)) if !host.is_empty() && port.parse::<u16>().is_ok() => Ok(()),
+        _ => Err("expected HOST:PORT, e.g. 127.0.0.1:8080".into()),
+    }
+}
+
+fn revocation_url(value: &str) -> std::result::Result<(), String> {
+    let url = Url::parse(value).map_err(|why| why.to_string())?;
+
+    if url.scheme() != "https" {
+        return Err("must be an https URL".into());
+    }
+
+    Ok(())
+}
+
+fn not_empty(value: &str) -> std::result::Result<(), String> {
+    if value.trim().is_empty() {
+        return Err("not set".into());
+    }
+
+    if value.chars().any(char::is_whitespace) {
+        return Err("must not contain whitespace".into());
+    }
+
+    Ok(())
+}
+
+/// Check that application keys match the configured environment, which
+/// eBay marks with `production` and `sandbox` in the key.
+fn keys_environment(
+    value: &str,
+    production: &str,
+    sandbox: &str,
+    environment: Environment,
+) -> std::result::Result<(), String> {
+    let mismatch = match environment {
+        Environment::Production => value.contains(sandbox),
+        Environment::Sandbox => value.contains(production),
+    };
+
+    if mismatch {
+        return Err(format!(
+            "belongs to a different environment than `{environment}`"
+        ));
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{check, require_credentials, Severity};
+    use crate::config::configuration::Configuration;
+
+    #[test]
+    fn unused_fields_are_warnings() {
+        let config = Configuration {
+            appid: "Example-App-PRD-0123456789".into(),
+            devid: "not-a-uuid".into(),
+            certid: "PRD-0123456789ab-cdef".into(),
+            ..Configuration::default()
+        };
+
+        assert!(require_credentials(&config).is_ok());
+
+        let problems = check(&config);
+        let fields: Vec<_> = problems.iter().map(|problem| problem.field).collect();
+        assert_eq!(fields, ["devid", "redirecturi"]);
+        assert!(problems
+            .iter()
+            .all(|problem| problem.severity == Severity::Warning));
+    }
+
+    #[test]
+    fn credentials_are_required() {
+        let config = Configuration {
+            appid: "Example-App-PRD-0123456789".into(),
+            ..Configuration::default()
+        };
+
+        assert!(require_credentials(&config).is_err());
+    }
+}
@@ -0,0 +1,209 @@
+use crate::{
+    cli::QrArgs,
+    error::{Error, Result},
+    oauth::{Authorization, OAuthClient},
+    qr, redirect,
+    tokenmgr::TokenResult,
+};
+use colored::Colorize;
+use log::{debug, info, warn};
+use nix::{
+    errno::Errno,
+    poll::{poll, PollFd, PollFlags, PollTimeout},
+    unistd::read,
+};
+use oauth2::{url::Url, AuthorizationCode};
+use std::{
+    io::stdin,
+    os::fd::{AsFd, AsRawFd},
+    time::{Duration, Instant},
+};
+
+/// Remaining times at which a reminder is printed.
+const REMINDERS: [Duration; 6] = [
+    Duration::from_secs(240),
+    Duration::from_secs(180),
+    Duration::from_secs(120),
+    Duration::from_secs(60),
+    Duration::from_secs(30),
+    Duration::from_secs(10),
+];
+
+/// Show the consent URL to the user, optionally as a QR code.
+pub fn show_url(url: &Url, qr: &QrArgs) -> Result<()> {
+    println!("\nPlease authenticate using the following URL:");
+    println!("{url}\n");
+
+    if qr.qr {
+        qr::print(url)?;
+    }
+
+    if let Some(path) = &qr.qr_file {
+        qr::save(url, path)?;
+    }
+
+    Ok(())
+}
+
+/// Run the authorization code flow interactively.
+///
+/// The redirect is received on `listen` if given, otherwise the user is asked
+/// to paste it into the terminal. A new consent URL is generated if consent
+/// is declined or not completed in time.
+pub fn authorize(
+    client: &OAuthClient,
+    scopes: &[Box<str>],
+    listen: Option<&str>,
+    open: bool,
+    qr: &QrArgs,
+) -> Result<TokenResult> {
+    let mut prompt = Prompt::new();
+
+    loop {
+        info!("Authorizing...");
+        let authorization = Authorization::new(client, scopes);
+
+        info!("Generated auth URL");
+        show_url(&authorization.url, qr)?;
+
+        if open {
+            redirect::open_browser(&authorization.url);
+        }
+
+        let result = match listen {
+            Some(address) => redirect::await_redirect(address, &authorization),
+            None => read_code(&mut prompt, &authorization),
+        };
+
+        let result = result.and_then(|auth_code| authorization.complete(client, auth_code));
+
+        match result {
+            Ok(token_result) => return Ok(token_result),
+            Err(
+                why @ (Error::AuthorizationExpired
+                | Error::ConsentDeclined(..)
+                | Error::CodeRejected),
+            ) => {
+                warn!("{why}");
+                println!("{}", "Starting a new authorization".yellow());
+            }
+            Err(why) => return Err(why),
+        }
+    }
+}
+
+/// Ask for the redirect URL until a valid one is entered or the
+/// authorization expires.
+fn read_code(prompt: &mut Prompt, authorization: &Authorization) -> Result<AuthorizationCode> {
+    debug!("Waiting for authentication");
+
+    loop {
+        let Some(input) = prompt.read_until(authorization.deadline())? else {
+            return Err(Error::AuthorizationExpired);
+        };
+
+        match authorization.parse_input(&input) {
+            Ok(auth_code) => return Ok(auth_code),
+            Err(why @ Error::ConsentDeclined(..)) => return Err(why),
+            Err(why) => eprintln!("{} {why}", "Invalid input:".red()),
+        }
+    }
+}
+
+/// Reads the post-consent redirect URL (or code) from the terminal.
+///
+/// Input is read without blocking past the authorization's deadline, so an
+/// expired authorization can be replaced instead of waiting forever.
+#[derive(Default)]
+pub struct Prompt {
+    buffer: Vec<u8>,
+}
+
+impl Prompt {
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Ask for input until `deadline`, printing a countdown while waiting.
+    ///
+    /// Returns `None` if the deadline passes first.
+    pub fn read_until(&mut self, deadline: Instant) -> Result<Option<String>> {
+        println!(
+            "Enter the URL after authentication ({} remaining):",
+            format_remaining(remaining(deadline))
+        );
+
+        let mut reminders = REMINDERS
+            .iter()
+            .filter(|reminder| **reminder < remaining(deadline))
+            .peekable();
+
+        loop {
+            if let Some(line) = self.take_line() {
+                return Ok(Some(line));
+            }
+
+            let left = remaining(deadline);
+            if left.is_zero() {
+                return Ok(None);
+            }
+
+            if reminders.next_if(|reminder| left <= **reminder).is_some() {
+                println!(
+                    "{}",
+                    format!("Authorization expires in {}", format_remaining(left)).yellow()
+                );
+            }
+
+            let wait = left.min(Duration::from_secs(1));
+            if !self.fill(wait)? {
+                continue;
+            }
+        }
+    }
+
+    /// Read from standard input if data arrives within `wait`.
+    fn fill(&mut self, wait: Duration) -> Result<bool> {
+        let stdin = stdin();
+        let mut fds = [PollFd::new(stdin.as_fd(), PollFlags::POLLIN)];
+        let timeout = PollTimeout::try_from(wait).unwrap_or(PollTimeout::MAX);
+
+        match poll(&mut fds, timeout) {
+            Ok(0) | Err(Errno::EINTR) => return Ok(false),
+            Ok(..) => (),
+            Err(why) => return Err(why.into()),
+        }
+
+        let mut chunk = [0; 1024];
+        let count = read(stdin.as_raw_fd(), &mut chunk)?;
+
+        if count == 0 {
+            // End of input, hand out whatever is left or give up
+            if self.buffer.is_empty() {
+                return Err(Error::InputClosed);
+            }
+
+            self.buffer.push(b'\n');
+        }
+
+        self.buffer.extend_from_slice(&chunk[..count]);
+        Ok(true)
+    }
+
+    fn take_line(&mut self) -> Option<String> {
+        let end = self.buffer.iter().position(|byte| *byte == b'\n')?;
+        let line: Vec<u8> = self.buffer.drain(..=end).collect();
+
+        Some(String::from_utf8_lossy(&line).trim().to_string())
+    }
+}
+
+fn remaining(deadline: Instant) -> Duration {
+    deadline.saturating_duration_since(Instant::now())
+}
+
+fn format_remaining(remaining: Duration) -> String {
+    let secs = remaining.as_secs();
+    format!("{}:{:02}", secs / 60, secs % 60)
+}
@@ -0,0 +1,149 @@
+use crate::{
+    error::{Error, Result},
+    tokenmgr::TokenResult,
+};
+use ebay_authd_core::secret::Secret;
+use log::debug;
+use oauth2::{RefreshToken, TokenResponse};
+use serde::{Deserialize, Serialize};
+use std::{
+    env,
+    fs::{self, DirBuilder, OpenOptions},
+    io::{self, Read, Write},
+    os::unix::fs::{DirBuilderExt, OpenOptionsExt},
+    path::Path,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+/// Environment variable holding a refresh token to seed the daemon with.
+pub const REFRESH_TOKEN_ENV: &str = "EBAY_AUTHD_REFRESH_TOKEN";
+
+/// A stored grant, as written by `auth login --output`.
+///
+/// Files containing only the bare refresh token are accepted as well, their
+/// scopes and expiry are unknown.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Credentials {
+    pub refresh_token: Secret,
+    /// Scopes granted to the refresh token
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub scopes: Vec<Box<str>>,
+    /// Time the grant was obtained
+    #[serde(default, with = "humantime_serde")]
+    pub obtained: Option<SystemTime>,
+    /// Time the refresh token expires
+    #[serde(default, with = "humantime_serde")]
+    pub expires: Option<SystemTime>,
+}
+
+impl Credentials {
+    /// Credentials for the grant in a token response to a request for the
+    /// `requested` scopes.
+    pub fn from_token(token: &TokenResult, requested: &[Box<str>]) -> Result<Self> {
+        let refresh_token = token.refresh_token().ok_or(Error::MissingRefreshToken)?;
+        // Timestamps are stored with second precision
+        let now = UNIX_EPOCH + Duration::from_secs(unix_time(SystemTime::now()));
+
+        // Without a scope in the response, the requested scopes were granted
+        let scopes = match token.scopes() {
+            Some(scopes) => scopes.iter().map(|scope| scope.as_str().into()).collect(),
+            None => requested.to_vec(),
+        };
+
+        Ok(Self {
+            refresh_token: refresh_token.secret().as_str().into(),
+            scopes,
+            obtained: Some(now),
+            expires: token
+                .extra_fields()
+                .refresh_token_expires_in
+                .map(|secs| now + Duration::from_secs(secs)),
+        })
+    }
+
+    /// Read credentials from `path`, or from standard input if it is `-`.
+    pub fn read(path: &Path) -> Result<Self> {
+        if path == Path::new("-") {
+            debug!("Reading credentials from standard input");
+            let mut contents = String::new();
+            io::stdin().read_to_string(&mut contents)?;
+
+            return Self::parse(&contents, "standard input");
+        }
+
+        debug!("Reading credentials from {}", path.display());
+        Self::parse(&fs::read_to_string(path)?, &path.display().to_string())
+    }
+
+    /// Write the credentials to `path`, readable only by the current user.
+    ///
+    /// The file is replaced atomically so a failed write never leaves a
+    /// truncated credential file behind.
+    pub fn write(&self, path: &Path) -> Result<()> {
+        if let Some(parent) = path
+            .parent()
+            .filter(|parent| !parent.as_os_str().is_empty() && !parent.exists())
+        {
+            DirBuilder::new()
+                .recursive(true)
+                .mode(0o700)
+                .create(parent)?;
+        }
+
+        let temp = path.with_extension("tmp");
+        let mut file = OpenOptions::new()
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .mode(0o600)
+            .open(&temp)?;
+
+        file.write_all(serde_yaml::to_string(self)?.as_bytes())?;
+        file.sync_all()?;
+        fs::rename(&temp, path)?;
+
+        debug!("Wrote credentials to {}", path.display());
+        Ok(())
+    }
+
+    #[must_use]
+    pub fn refresh_token(&self) -> RefreshToken {
+        RefreshToken::new(self.refresh_token.expose().to_string())
+    }
+
+    fn parse(contents: &str, source: &str) -> Result<Self> {
+        if let Ok(credentials) = serde_yaml::from_str::<Self>(contents) {
+            return Ok(credentials);
+        }
+
+        let token = contents.trim();
+
+        if token.is_empty() {
+            return Err(Error::EmptyRefreshToken(source.into()));
+        }
+
+        Ok(Self {
+            refresh_token: token.into(),
+            scopes: Vec::new(),
+            obtained: None,
+            expires: None,
+        })
+    }
+}
+
+/// Refresh token from the [`REFRESH_TOKEN_ENV`] environment variable, if set.
+pub fn from_env() -> Result<Option<RefreshToken>> {
+    env::var_os(REFRESH_TOKEN_ENV)
+        .map(|value| {
+            Credentials::parse(&value.to_string_lossy(), REFRESH_TOKEN_ENV)
+                .map(|credentials| credentials.refresh_token())
+        })
+        .transpose()
+}
+
+/// Seconds since the Unix epoch.
+fn unix_time(time: SystemTime) -> u64 {
+    time.duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+}
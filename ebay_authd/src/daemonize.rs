@@ -0,0 +1,62 @@
+use crate::{
+    error::Result,
+    instance::InstanceLock,
+    logger::{self, RotatingFile},
+};
+use log::info;
+use nix::{
+    sys::wait::waitpid,
+    unistd::{chdir, dup2, fork, setsid, ForkResult},
+};
+use std::{fs::File, os::fd::AsRawFd, path::Path, process::exit};
+
+/// Move the current process into the background.
+///
+/// The calling process exits once the daemon process has been forked. In the
+/// daemon process, standard input is redirected from `/dev/null`, standard
+/// output and error are appended to `log_file`, which also receives the log
+/// unless it goes to the journal, and the pid file is updated.
+pub fn detach(lock: &mut InstanceLock, log_file: &Path, max_size: u64, keep: usize) -> Result<()> {
+    let log = RotatingFile::open(log_file, max_size, keep)?;
+    let null = File::open("/dev/null")?;
+
+    // SAFETY: no other threads are running at this point
+    if let ForkResult::Parent { child } = unsafe { fork() }? {
+        waitpid(child, None)?;
+        exit(0);
+    }
+
+    setsid()?;
+
+    // Fork again so the daemon can never reacquire a controlling terminal
+    // SAFETY: see above
+    if let ForkResult::Parent { child } = unsafe { fork() }? {
+        info!("Daemon running in background (pid {child})");
+        info!("Logging to {}", log_file.display());
+        exit(0);
+    }
+
+    lock.update_pid()?;
+    redirect(log, &null)
+}
+
+/// Detach the refresh helper from the terminal along with the socket server.
+///
+/// Unlike [`detach`], the process is not forked, but it does give up its
+/// terminal and log to `log_file` like the daemon process.
+pub fn detach_helper(log_file: &Path, max_size: u64, keep: usize) -> Result<()> {
+    let log = RotatingFile::open(log_file, max_size, keep)?;
+    let null = File::open("/dev/null")?;
+
+    setsid()?;
+    redirect(log, &null)
+}
+
+fn redirect(log: RotatingFile, null: &File) -> Result<()> {
+    chdir("/")?;
+
+    dup2(null.as_raw_fd(), 0)?;
+    logger::log_to_file(log.redirect_stdio()?);
+
+    Ok(())
+}
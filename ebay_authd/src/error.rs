@@ -1,14 +1,38 @@
-use confy::ConfyError;
-use std::{io, string::FromUtf8Error};
+use ebay_authd_core::state::State;
+use std::{io, path::PathBuf, string::FromUtf8Error};
 use thiserror::Error;
 
 #[derive(Debug, Error)]
 pub enum Error {
-    #[error("Could not find home directory path of current user")]
-    NoHome,
+    #[error("Failed to parse configuration {}: {reason}", path.display())]
+    ConfigParse { path: PathBuf, reason: Box<str> },
 
-    #[error("Config error: {0}")]
-    Config(#[from] ConfyError),
+    #[error("Unsupported configuration: {0}")]
+    ConfigVersion(Box<str>),
+
+    #[error("Failed to serialize configuration: {0}")]
+    ConfigSerialize(Box<str>),
+
+    #[error(
+        "Invalid configuration: {0}, run `ebay_authd config init` or `ebay_authd config validate`"
+    )]
+    InvalidConfig(Box<str>),
+
+    #[error("No configuration file location, use --config or EBAY_AUTHD_CONFIG")]
+    NoConfigLocation,
+
+    #[error("Configuration file {} already exists, use --force to overwrite it", .0.display())]
+    ConfigExists(PathBuf),
+
+    #[error("Invalid value in {var}: {reason}")]
+    EnvOverride { var: Box<str>, reason: Box<str> },
+
+    #[error("Failed to resolve `{field}` from `{reference}`: {reason}")]
+    Secret {
+        field: &'static str,
+        reference: Box<str>,
+        reason: Box<str>,
+    },
 
     #[error("URL parse error: {0}")]
     UrlParse(#[from] oauth2::url::ParseError),
@@ -19,9 +43,63 @@ pub enum Error {
     #[error("Failed to exchange auth code for token")]
     TokenRequest,
 
+    #[error("No authorization code found in input")]
+    MissingCode,
+
+    #[error("Consent was declined: {0}")]
+    ConsentDeclined(Box<str>),
+
+    #[error("Redirect does not belong to this authorization request (state mismatch)")]
+    StateMismatch,
+
+    #[error("Authorization expired, consent must be completed within 5 minutes")]
+    AuthorizationExpired,
+
+    #[error("Authorization code was rejected, it may have expired or already been used")]
+    CodeRejected,
+
+    #[error("Input closed before authorization completed")]
+    InputClosed,
+
+    #[error("Daemon is not authenticated")]
+    NotAuthenticated,
+
+    #[error("No authorization in progress")]
+    NoAuthorizationPending,
+
+    #[error("Refresh token was rejected, reauthentication required")]
+    GrantRejected,
+
+    #[error("Refresh token was rejected by eBay, it may have expired or been revoked")]
+    RefreshTokenRejected,
+
+    #[error("Token response did not include a refresh token")]
+    MissingRefreshToken,
+
+    #[error("No refresh token found in {0}")]
+    EmptyRefreshToken(Box<str>),
+
+    #[error("No refresh token given, use --refresh-token-file or set EBAY_AUTHD_REFRESH_TOKEN")]
+    NoRefreshToken,
+
+    #[error("Failed to serialize credentials: {0}")]
+    Credentials(#[from] serde_yaml::Error),
+
+    #[error("No revocation URL configured, revoke access in your eBay account settings")]
+    NoRevocationUrl,
+
+    #[error("Failed to revoke token")]
+    Revocation,
+
     #[error("Stop requested (not an error)")]
     StopRequested,
 
+    #[error("Daemon error: {0}")]
+    Daemon(Box<str>),
+
+    #[error("Daemon is not ready: {0}")]
+    NotReady(State),
+
     #[error("Unexpected response")]
     UnexpectedResponse,
 
@@ -31,11 +109,38 @@ pub enum Error {
     #[error("Client error: {0}")]
     Client(#[from] ebay_authd_client::error::Error),
 
-    #[error("Error executing `screen`")]
-    Screen,
+    #[error("Error executing `{0}`")]
+    Multiplexer(&'static str),
+
+    #[error(
+        "Another daemon is already running{}",
+        .0.map(|pid| format!(" (pid {pid})")).unwrap_or_default()
+    )]
+    AlreadyRunning(Option<i32>),
 
     #[error("Failed to convert to UTF-8: {0}")]
     Utf8(#[from] FromUtf8Error),
+
+    #[error("Failed to encode QR code: {0}")]
+    Qr(#[from] qrcode::types::QrError),
+
+    #[error("Failed to write QR code image: {0}")]
+    QrImage(#[from] image::ImageError),
+
+    #[error("Unsupported QR code file {}, use a .png or .svg extension", .0.display())]
+    QrFormat(PathBuf),
+
+    #[error("Failed to set up sandbox: {0}")]
+    Sandbox(Box<str>),
+
+    #[error("Refresh helper: {0}")]
+    Helper(Box<str>),
+
+    #[error("Refresh helper exited")]
+    HelperExited,
+
+    #[error("Failed to switch user: {0}")]
+    User(Box<str>),
 }
 
 pub type Result<T> = ::std::result::Result<T, Error>;
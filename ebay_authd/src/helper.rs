@@ -0,0 +1,756 @@
+use crate::{
+    config::{self, configuration::Configuration, validate},
+    error::{Error, Result},
+    logger,
+    oauth::{self, Authorization, OAuthClient},
+    sandbox::Sandbox,
+    signals,
+    tokenmgr::TokenManager,
+};
+use ebay_authd_core::secret::Secret;
+use log::{debug, error, info, warn, LevelFilter};
+use nix::{
+    sys::wait::waitpid,
+    unistd::{fork, ForkResult, Pid},
+};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use std::{
+    fs,
+    io::{Read, Write},
+    net::Shutdown,
+    os::unix::net::UnixStream,
+    path::{Path, PathBuf},
+    process::exit,
+    time::{Duration, Instant},
+};
+use zeroize::{Zeroize, Zeroizing};
+
+/// Initial size of the message buffers, see `ebay_authd_client::Client`.
+const BUFFER_SIZE: usize = 8192;
+
+/// Requests of the socket server to the helper.
+#[derive(Debug, Serialize, Deserialize)]
+enum Command {
+    /// Refresh the access token
+    Refresh,
+    /// Start an authorization for `scopes`
+    BeginAuth(Box<[Box<str>]>),
+    /// Complete the pending authorization with the redirect URL or code
+    CompleteAuth(Secret),
+    /// Forget the grant and revoke it at the provider
+    Revoke,
+    /// Load the configuration and apply new client credentials
+    Reload,
+    SetLogLevel(LevelFilter),
+}
+
+/// Answers of the helper.
+#[derive(Debug, Serialize, Deserialize)]
+enum Reply {
+    /// The helper is set up and waiting for commands
+    Ready {
+        /// The configuration, without the Cert ID
+        config: Configuration,
+        access: Option<Access>,
+    },
+    Token(Access),
+    AuthUrl(Box<str>),
+    /// Whether there was a grant to revoke
+    Revoked(bool),
+    /// The new configuration, without the Cert ID
+    Config(Configuration),
+    /// The command succeeded without a result
+    Done,
+    Failed(Failure),
+}
+
+/// Errors of the helper the socket server acts on.
+#[derive(Debug, Serialize, Deserialize)]
+enum Failure {
+    NotAuthenticated,
+    NoAuthorizationPending,
+    AuthorizationExpired,
+    MissingCode,
+    StateMismatch,
+    ConsentDeclined(Box<str>),
+    CodeRejected,
+    GrantRejected,
+    Other(Box<str>),
+}
+
+impl From<Error> for Failure {
+    fn from(error: Error) -> Self {
+        match error {
+            Error::NotAuthenticated => Self::NotAuthenticated,
+            Error::NoAuthorizationPending => Self::NoAuthorizationPending,
+            Error::AuthorizationExpired => Self::AuthorizationExpired,
+            Error::MissingCode => Self::MissingCode,
+            Error::StateMismatch => Self::StateMismatch,
+            Error::ConsentDeclined(reason) => Self::ConsentDeclined(reason),
+            Error::CodeRejected => Self::CodeRejected,
+            Error::GrantRejected => Self::GrantRejected,
+            other => Self::Other(other.to_string().into()),
+        }
+    }
+}
+
+impl From<Failure> for Error {
+    fn from(failure: Failure) -> Self {
+        match failure {
+            Failure::NotAuthenticated => Self::NotAuthenticated,
+            Failure::NoAuthorizationPending => Self::NoAuthorizationPending,
+            Failure::AuthorizationExpired => Self::AuthorizationExpired,
+            Failure::MissingCode => Self::MissingCode,
+            Failure::StateMismatch => Self::StateMismatch,
+            Failure::ConsentDeclined(reason) => Self::ConsentDeclined(reason),
+            Failure::CodeRejected => Self::CodeRejected,
+            Failure::GrantRejected => Self::GrantRejected,
+            Failure::Other(reason) => Self::Helper(reason),
+        }
+    }
+}
+
+/// An access token handed to the socket server, along with what it may
+/// report about the grant.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Access {
+    token: Secret,
+    expires_in: Duration,
+    token_fingerprint: Box<str>,
+    refresh_token_fingerprint: Box<str>,
+    #[serde(skip, default = "Instant::now")]
+    received: Instant,
+}
+
+impl Access {
+    #[must_use]
+    pub fn new(tman: &TokenManager) -> Self {
+        Self {
+            token: tman.get_token(),
+            expires_in: tman.expiry(),
+            token_fingerprint: tman.token_fingerprint(),
+            refresh_token_fingerprint: tman.refresh_token_fingerprint(),
+            received: Instant::now(),
+        }
+    }
+
+    #[must_use]
+    pub fn get_token(&self) -> Secret {
+        self.token.clone()
+    }
+
+    #[must_use]
+    pub fn expires_soon(&self) -> bool {
+        self.expiry() <= Duration::from_secs(10)
+    }
+
+    #[must_use]
+    pub fn is_expired(&self) -> bool {
+        self.expiry().is_zero()
+    }
+
+    #[must_use]
+    pub fn expiry(&self) -> Duration {
+        self.expires_in.saturating_sub(self.received.elapsed())
+    }
+
+    #[must_use]
+    pub fn last_refresh(&self) -> Duration {
+        self.received.elapsed()
+    }
+
+    #[must_use]
+    pub fn token_fingerprint(&self) -> Box<str> {
+        self.token_fingerprint.clone()
+    }
+
+    #[must_use]
+    pub fn refresh_token_fingerprint(&self) -> Box<str> {
+        self.refresh_token_fingerprint.clone()
+    }
+}
+
+/// The socket server's end of the refresh helper.
+///
+/// The helper is a child process holding the refresh token and the Cert ID.
+/// It performs refreshes, authorizations and revocations on request, and only
+/// ever hands out access tokens. It exits once the socket server closes its
+/// end.
+pub struct Helper {
+    channel: Channel,
+    pid: Pid,
+    /// The helper exited or the channel to it broke
+    exited: bool,
+}
+
+impl Helper {
+    /// Fork the helper and wait until it obtained the grant.
+    ///
+    /// The helper resolves the secrets in `config`, creates the client and
+    /// runs `startup` with it to obtain the grant, `None` to start without
+    /// one. It then runs `detach` and enters `sandbox`. The configuration,
+    /// without the Cert ID, and the first access token are returned.
+    ///
+    /// The helper reloads the configuration from `location`, and deletes the
+    /// `credentials` file the grant was read from when asked to revoke it.
+    /// This process never holds the grant, nor the Cert ID if it is given as
+    /// a reference other than `env:`.
+    pub fn spawn(
+        location: Option<&Path>,
+        credentials: Option<&Path>,
+        mut config: Configuration,
+        sandbox: Sandbox,
+        startup: impl FnOnce(&OAuthClient, &Configuration) -> Result<Option<TokenManager>>,
+        detach: impl FnOnce() -> Result<()>,
+    ) -> Result<(Self, Configuration, Option<Access>)> {
+        let (server, helper) = UnixStream::pair()?;
+
+        // SAFETY: no other threads are running at this point
+        match unsafe { fork() }? {
+            ForkResult::Child => {
+                drop(server);
+                let mut channel = Channel::new(helper);
+
+                let grant = Grant::new(location, credentials, config, startup, detach, sandbox);
+                let code = match grant {
+                    Ok(grant) => {
+                        grant.serve(channel);
+                        0
+                    }
+                    Err(why) => {
+                        let _ = channel.send(&Reply::Failed(why.into()));
+                        1
+                    }
+                };
+                exit(code);
+            }
+            ForkResult::Parent { child } => {
+                drop(helper);
+                // Zeroes this copy of a literal Cert ID, but not those left
+                // behind by reading the configuration file
+                config.certid = Secret::default();
+
+                let mut helper = Self {
+                    channel: Channel::new(server),
+                    pid: child,
+                    exited: false,
+                };
+
+                match helper.channel.receive()? {
+                    Some(Reply::Ready { config, access }) => {
+                        info!("Refresh helper running (pid {child})");
+                        Ok((helper, config, access))
+                    }
+                    Some(Reply::Failed(failure)) => Err(failure.into()),
+                    _ => Err(Error::Helper("exited during startup".into())),
+                }
+            }
+        }
+    }
+
+    pub fn refresh(&mut self) -> Result<Access> {
+        match self.exchange(Command::Refresh)? {
+            Reply::Token(access) => Ok(access),
+            _ => Err(Error::UnexpectedResponse),
+        }
+    }
+
+    /// Start an authorization for `scopes` and return the consent URL.
+    pub fn begin_auth(&mut self, scopes: &[Box<str>]) -> Result<Box<str>> {
+        match self.exchange(Command::BeginAuth(scopes.into()))? {
+            Reply::AuthUrl(url) => Ok(url),
+            _ => Err(Error::UnexpectedResponse),
+        }
+    }
+
+    pub fn complete_auth(&mut self, input: Secret) -> Result<Access> {
+        match self.exchange(Command::CompleteAuth(input))? {
+            Reply::Token(access) => Ok(access),
+            _ => Err(Error::UnexpectedResponse),
+        }
+    }
+
+    /// Revoke the grant, returning whether there was one.
+    pub fn revoke(&mut self) -> Result<bool> {
+        match self.exchange(Command::Revoke)? {
+            Reply::Revoked(revoked) => Ok(revoked),
+            _ => Err(Error::UnexpectedResponse),
+        }
+    }
+
+    /// Load and validate the configuration, applying new client credentials
+    /// in the helper. The returned configuration has an empty Cert ID.
+    pub fn reload(&mut self) -> Result<Configuration> {
+        match self.exchange(Command::Reload)? {
+            Reply::Config(config) => Ok(config),
+            _ => Err(Error::UnexpectedResponse),
+        }
+    }
+
+    pub fn set_log_level(&mut self, level: LevelFilter) -> Result<()> {
+        match self.exchange(Command::SetLogLevel(level))? {
+            Reply::Done => Ok(()),
+            _ => Err(Error::UnexpectedResponse),
+        }
+    }
+
+    /// Whether the helper is gone, it can't be restarted without the grant.
+    #[must_use]
+    pub fn exited(&self) -> bool {
+        self.exited
+    }
+
+    fn exchange(&mut self, command: Command) -> Result<Reply> {
+        if self.exited {
+            return Err(Error::HelperExited);
+        }
+
+        let reply = self
+            .channel
+            .send(&command)
+            .and_then(|()| self.channel.receive());
+
+        match reply {
+            Ok(Some(Reply::Failed(failure))) => Err(failure.into()),
+            Ok(Some(reply)) => Ok(reply),
+            Ok(None) => {
+                self.exited = true;
+                Err(Error::HelperExited)
+            }
+            Err(why) => {
+                // Replies can't be matched to commands anymore
+                error!("Lost the refresh helper: {why}");
+                self.exited = true;
+                Err(Error::HelperExited)
+            }
+        }
+    }
+}
+
+impl Drop for Helper {
+    fn drop(&mut self) {
+        let _ = self.channel.stream.shutdown(Shutdown::Both);
+
+        if let Err(why) = waitpid(self.pid, None) {
+            debug!("Failed to wait for refresh helper: {why}");
+        }
+    }
+}
+
+/// The grant, as held by the helper process.
+struct Grant {
+    client: OAuthClient,
+    tman: Option<TokenManager>,
+    authorization: Option<Authorization>,
+    location: Option<PathBuf>,
+    /// The credential file the grant was read from
+    credentials: Option<PathBuf>,
+    config: Configuration,
+}
+
+impl Grant {
+    fn new(
+        location: Option<&Path>,
+        credentials: Option<&Path>,
+        config: Configuration,
+        startup: impl FnOnce(&OAuthClient, &Configuration) -> Result<Option<TokenManager>>,
+        detach: impl FnOnce() -> Result<()>,
+        mut sandbox: Sandbox,
+    ) -> Result<Self> {
+        let config = config::resolve_secrets(config)?;
+
+        info!("Creating client");
+        let client = oauth::client(&config)?;
+        let tman = startup(&client, &config)?;
+
+        detach()?;
+        // Stopping is up to the socket server, which may still revoke
+        signals::ignore()?;
+        sandbox.allow_network();
+        if let Some(parent) = credentials.and_then(Path::parent) {
+            sandbox.allow_remove(parent);
+        }
+        sandbox.apply()?;
+
+        Ok(Self {
+            client,
+            tman,
+            authorization: None,
+            location: location.map(Path::to_path_buf),
+            credentials: credentials.map(Path::to_path_buf),
+            config,
+        })
+    }
+
+    /// Answer commands until the socket server goes away.
+    fn serve(mut self, mut channel: Channel) {
+        let ready = Reply::Ready {
+            config: Configuration {
+                certid: Secret::default(),
+                ..self.config.clone()
+            },
+            access: self.tman.as_ref().map(Access::new),
+        };
+
+        if channel.send(&ready).is_err() {
+            return;
+        }
+
+        loop {
+            let command = match channel.receive::<Command>() {
+                Ok(Some(command)) => command,
+                Ok(None) => break,
+                Err(why) => {
+                    error!("Refresh helper failed to read command: {why}");
+                    break;
+                }
+            };
+
+            let reply = self
+                .handle(command)
+                .unwrap_or_else(|why| Reply::Failed(why.into()));
+
+            if let Err(why) = channel.send(&reply) {
+                error!("Refresh helper failed to reply: {why}");
+                break;
+            }
+        }
+
+        debug!("Refresh helper exiting");
+    }
+
+    fn handle(&mut self, command: Command) -> Result<Reply> {
+        match command {
+            Command::Refresh => {
+                let tman = self.tman.as_mut().ok_or(Error::NotAuthenticated)?;
+
+                if let Err(why) = tman.refresh() {
+                    if matches!(why, Error::GrantRejected) {
+                        self.tman = None;
+                    }
+                    return Err(why);
+                }
+
+                Ok(Reply::Token(Access::new(tman)))
+            }
+            Command::BeginAuth(scopes) => {
+                let authorization = Authorization::new(&self.client, &scopes);
+                let url = authorization.url.as_str().into();
+                self.authorization = Some(authorization);
+
+                Ok(Reply::AuthUrl(url))
+            }
+            Command::CompleteAuth(input) => {
+                let authorization = self
+                    .authorization
+                    .take()
+                    .ok_or(Error::NoAuthorizationPending)?;
+
+                if authorization.is_expired() {
+                    return Err(Error::AuthorizationExpired);
+                }
+
+                // Malformed input keeps the authorization pending
+                let auth_code = match authorization.parse_input(input.expose()) {
+                    Ok(auth_code) => auth_code,
+                    Err(why @ (Error::MissingCode | Error::StateMismatch)) => {
+                        self.authorization = Some(authorization);
+                        return Err(why);
+                    }
+                    Err(why) => return Err(why),
+                };
+
+                let token_result = authorization.complete(&self.client, auth_code)?;
+                let tman = TokenManager::new(self.client.clone(), token_result)?;
+                let access = Access::new(&tman);
+
+                if self.tman.replace(tman).is_some() {
+                    info!("Replaced grant with the new authorization");
+                }
+                // Revoking no longer concerns the grant in the file
+                self.credentials = None;
+
+                Ok(Reply::Token(access))
+            }
+            Command::Revoke => {
+                self.authorization = None;
+
+                let tman = self.tman.take();
+
+                // The tokens are forgotten even if the provider can't revoke
+                // them, so the file must not keep them either
+                if let Some(path) = self.credentials.take() {
+                    match fs::remove_file(&path) {
+                        Ok(()) => info!("Deleted {}", path.display()),
+                        Err(why) => warn!("Failed to delete {}: {why}", path.display()),
+                    }
+                }
+
+                match tman {
+                    Some(tman) => tman.revoke().map(|()| Reply::Revoked(true)),
+                    None => Ok(Reply::Revoked(false)),
+                }
+            }
+            Command::Reload => {
+                let mut new = config::load(self.location.as_deref())?;
+                validate::require_valid(&new)?;
+
+                if new.certid != self.config.certid
+                    || new.revocation_url != self.config.revocation_url
+                {
+                    info!("Applying new client credentials");
+                    // The other fields of the client take effect after a
+                    // restart
+                    let config = Configuration {
+                        certid: new.certid.clone(),
+                        revocation_url: new.revocation_url.clone(),
+                        ..self.config.clone()
+                    };
+                    self.client = oauth::client(&config)?;
+                    self.config = config;
+
+                    if let Some(tman) = &mut self.tman {
+                        tman.set_client(self.client.clone());
+                    }
+                }
+
+                new.certid = Secret::default();
+                Ok(Reply::Config(new))
+            }
+            Command::SetLogLevel(level) => {
+                logger::set_level(level);
+                Ok(Reply::Done)
+            }
+        }
+    }
+}
+
+/// Newline delimited JSON over one end of the socket pair.
+///
+/// Like `ebay_authd_client::Client`, messages are buffered in memory that is
+/// overwritten with zeros once they have been handled.
+struct Channel {
+    stream: UnixStream,
+    buffer: Zeroizing<Vec<u8>>,
+}
+
+impl Channel {
+    fn new(stream: UnixStream) -> Self {
+        Self {
+            stream,
+            buffer: Zeroizing::new(Vec::with_capacity(BUFFER_SIZE)),
+        }
+    }
+
+    fn send<T: Serialize>(&mut self, message: &T) -> Result<()> {
+        let mut json = Zeroizing::new(Vec::with_capacity(BUFFER_SIZE));
+        serde_json::to_writer(&mut *json, message)
+            .map_err(|why| Error::Helper(why.to_string().into()))?;
+        json.push(b'\n');
+
+        self.stream.write_all(&json)?;
+        Ok(())
+    }
+
+    /// Wait for the next message, `None` if the other end was closed.
+    fn receive<T: DeserializeOwned>(&mut self) -> Result<Option<T>> {
+        let end = loop {
+            if let Some(end) = self.buffer.iter().position(|&byte| byte == b'\n') {
+                break end;
+            }
+
+            let mut chunk = Zeroizing::new([0; 1024]);
+            let read = self.stream.read(&mut *chunk)?;
+            if read == 0 {
+                return Ok(None);
+            }
+
+            self.buffer.extend_from_slice(&chunk[..read]);
+        };
+
+        let message = serde_json::from_slice(&self.buffer[..end])
+            .map_err(|why| Error::Helper(why.to_string().into()));
+        self.consume(end + 1);
+
+        message.map(Some)
+    }
+
+    /// Remove the first `len` bytes from the buffer, zeroing the memory they
+    /// occupied.
+    fn consume(&mut self, len: usize) {
+        let len = len.min(self.buffer.len());
+        self.buffer[..len].zeroize();
+        self.buffer.drain(..len);
+
+        let remaining = self.buffer.len();
+        let capacity = self.buffer.capacity();
+        self.buffer.resize(capacity, 0);
+        self.buffer.truncate(remaining);
+    }
+}
+
+#[cfg(all(test, target_os = "linux"))]
+mod tests {
+    use super::{Channel, Command, Grant, Helper};
+    use crate::{
+        config::configuration::Configuration,
+        error::Error,
+        oauth::OAuthClient,
+        sandbox::{Sandbox, SandboxMode},
+        tokenmgr::{TokenManager, TokenResult},
+    };
+    use ebay_authd_core::secret::Secret;
+    use nix::unistd::Pid;
+    use oauth2::{basic::BasicTokenType, AccessToken, AuthUrl, ClientId, RefreshToken, TokenUrl};
+    use std::{
+        env,
+        fs::{self, File},
+        os::unix::fs::FileExt,
+        os::unix::net::UnixStream,
+        process,
+        time::Duration,
+    };
+
+    const CERTID: &str = "PRD-helper0test-cert-0123-4567-89ab";
+    const REFRESH_TOKEN: &str = "v^1.1#i^1#helper-test-refresh-token";
+    const ACCESS_TOKEN: &str = "v^1.1#i^1#helper-test-access-token";
+    /// Set in the test binary re-executed to run a single test.
+    const CHILD_ENV: &str = "HELPER_TEST_CHILD";
+
+    fn token() -> TokenResult {
+        let mut token = TokenResult::new(
+            AccessToken::new(ACCESS_TOKEN.into()),
+            BasicTokenType::Bearer,
+            Default::default(),
+        );
+        token.set_expires_in(Some(&Duration::from_secs(7200)));
+        token.set_refresh_token(Some(RefreshToken::new(REFRESH_TOKEN.into())));
+        token
+    }
+
+    /// Whether `needle` is in any writable memory of this process.
+    fn memory_contains(needle: &[u8]) -> bool {
+        let maps = fs::read_to_string("/proc/self/maps").unwrap();
+        let memory = File::open("/proc/self/mem").unwrap();
+
+        maps.lines().any(|line| {
+            let mut fields = line.split_whitespace();
+            let (Some(range), Some(perms)) = (fields.next(), fields.next()) else {
+                return false;
+            };
+            let Some((start, end)) = range.split_once('-') else {
+                return false;
+            };
+            if !perms.starts_with("rw") || line.ends_with("[vvar]") {
+                return false;
+            }
+
+            let start = u64::from_str_radix(start, 16).unwrap();
+            let end = u64::from_str_radix(end, 16).unwrap();
+            let mut region = vec![0; (end - start) as usize];
+
+            memory.read_exact_at(&mut region, start).is_ok()
+                && region.windows(needle.len()).any(|window| window == needle)
+        })
+    }
+
+    #[test]
+    fn server_holds_no_secrets() {
+        // Forking in a multi-threaded test runner may deadlock the helper, so
+        // scan a test binary that runs nothing else
+        if env::var_os(CHILD_ENV).is_none() {
+            let output = process::Command::new(env::current_exe().unwrap())
+                .args(["--exact", "helper::tests::server_holds_no_secrets"])
+                .arg("--test-threads=1")
+                .env(CHILD_ENV, "1")
+                .output()
+                .unwrap();
+            let stdout = String::from_utf8_lossy(&output.stdout);
+            assert!(output.status.success(), "{stdout}");
+            assert!(stdout.contains("1 passed"), "{stdout}");
+            return;
+        }
+
+        let dir = env::temp_dir().join(format!("ebay_authd-helper-test-{}", process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let certid = dir.join("certid");
+        fs::write(&certid, CERTID).unwrap();
+
+        let config = Configuration {
+            appid: "Example-App-PRD-0123456789".into(),
+            devid: "01234567-89ab-cdef-0123-456789abcdef".into(),
+            certid: Secret::from(format!("file:{}", certid.display())),
+            redirecturi: "Example-App-Example-abcdef".into(),
+            ..Configuration::default()
+        };
+
+        let (helper, config, access) = Helper::spawn(
+            None,
+            None,
+            config,
+            Sandbox::new(SandboxMode::Off),
+            |client, _| TokenManager::new(client.clone(), token()).map(Some),
+            || Ok(()),
+        )
+        .unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert!(config.certid.is_empty());
+        assert!(access.is_some());
+        // The scan finds what this process does hold
+        assert!(memory_contains(ACCESS_TOKEN.as_bytes()));
+        assert!(!memory_contains(CERTID.as_bytes()));
+        assert!(!memory_contains(REFRESH_TOKEN.as_bytes()));
+
+        drop(helper);
+    }
+
+    #[test]
+    fn revoke_deletes_credentials_without_provider() {
+        let dir = env::temp_dir().join(format!("ebay_authd-revoke-test-{}", process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let credentials = dir.join("credentials");
+        fs::write(&credentials, "{}").unwrap();
+
+        // Without a revocation URL
+        let client = OAuthClient::new(
+            ClientId::new("Example-App-PRD-0123456789".into()),
+            None,
+            AuthUrl::new("https://auth.example.com/authorize".into()).unwrap(),
+            Some(TokenUrl::new("https://api.example.com/token".into()).unwrap()),
+        );
+        let mut grant = Grant {
+            tman: Some(TokenManager::new(client.clone(), token()).unwrap()),
+            client,
+            authorization: None,
+            location: None,
+            credentials: Some(credentials.clone()),
+            config: Configuration::default(),
+        };
+
+        let result = grant.handle(Command::Revoke);
+        let deleted = !credentials.exists();
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert!(matches!(result, Err(Error::NoRevocationUrl)));
+        assert!(deleted);
+        assert!(grant.tman.is_none());
+    }
+
+    #[test]
+    fn exited_helper_is_reported() {
+        let (server, helper) = UnixStream::pair().unwrap();
+        drop(helper);
+
+        let mut helper = Helper {
+            channel: Channel::new(server),
+            // Not a child, waiting for it fails on drop
+            pid: Pid::from_raw(i32::MAX),
+            exited: false,
+        };
+
+        assert!(matches!(helper.refresh(), Err(Error::HelperExited)));
+        assert!(helper.exited());
+        assert!(matches!(helper.reload(), Err(Error::HelperExited)));
+    }
+}
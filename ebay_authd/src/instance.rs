@@ -0,0 +1,149 @@
+use crate::error::{Error, Result};
+use ebay_authd_client::discovery::{self, SocketAddress};
+use log::{debug, warn};
+use nix::{
+    errno::Errno,
+    fcntl::{Flock, FlockArg},
+    unistd::Pid,
+};
+use std::{
+    fs::{self, DirBuilder, File, OpenOptions},
+    io::{ErrorKind, Read, Seek, Write},
+    os::unix::fs::{DirBuilderExt, OpenOptionsExt},
+    path::{Path, PathBuf},
+};
+
+/// Exclusive lock on the pid file belonging to a socket address.
+///
+/// Only one daemon can hold the lock for a given socket. The lock is
+/// released and the pid file removed when this is dropped.
+pub struct InstanceLock {
+    file: Flock<File>,
+    path: PathBuf,
+}
+
+impl InstanceLock {
+    pub fn acquire(socket: &SocketAddress) -> Result<Self> {
+        let path = pid_file(socket);
+        create_parent(&path)?;
+
+        debug!("Locking {}", path.display());
+        let file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .mode(0o600)
+            .open(&path)?;
+
+        let mut file = match Flock::lock(file, FlockArg::LockExclusiveNonblock) {
+            Ok(file) => file,
+            Err((mut file, Errno::EWOULDBLOCK)) => {
+                return Err(Error::AlreadyRunning(read_pid(&mut file)));
+            }
+            Err((_, errno)) => return Err(errno.into()),
+        };
+
+        file.set_len(0)?;
+        write!(file, "{}", Pid::this())?;
+        file.flush()?;
+
+        Ok(Self { file, path })
+    }
+
+    /// Rewrite the pid file, e.g. after forking.
+    pub fn update_pid(&mut self) -> Result<()> {
+        self.file.set_len(0)?;
+        self.file.rewind()?;
+        write!(self.file, "{}", Pid::this())?;
+        self.file.flush()?;
+
+        Ok(())
+    }
+}
+
+impl Drop for InstanceLock {
+    fn drop(&mut self) {
+        if let Err(why) = fs::remove_file(&self.path) {
+            warn!("Failed to remove pid file: {why}");
+        }
+    }
+}
+
+/// Prepare `socket` for binding.
+///
+/// Refuses to continue if a daemon is still serving on the socket and
+/// removes the socket file if it is stale.
+pub fn prepare_socket(socket: &SocketAddress) -> Result<()> {
+    if socket.connect().is_ok() {
+        return Err(Error::AlreadyRunning(read_pid_file(socket)));
+    }
+
+    let Some(path) = socket.path() else {
+        return Ok(());
+    };
+
+    create_parent(path)?;
+
+    match fs::remove_file(path) {
+        Ok(()) => warn!("Removed stale socket {}", path.display()),
+        Err(why) if why.kind() == ErrorKind::NotFound => (),
+        Err(why) => return Err(why.into()),
+    }
+
+    Ok(())
+}
+
+/// Pid of the daemon serving `socket`, if its pid file exists.
+#[must_use]
+pub fn read_pid_file(socket: &SocketAddress) -> Option<i32> {
+    File::open(pid_file(socket))
+        .ok()
+        .and_then(|mut file| read_pid(&mut file))
+}
+
+/// Location of the pid file used for `socket`.
+///
+/// This is the socket path with a `.pid` extension. Abstract sockets use
+/// the default socket directory.
+#[must_use]
+pub fn pid_file(socket: &SocketAddress) -> PathBuf {
+    match socket {
+        SocketAddress::Path(path) => path.with_extension("pid"),
+        SocketAddress::Abstract(name) => {
+            let SocketAddress::Path(default) = discovery::default_socket() else {
+                unreachable!("default socket is always a path");
+            };
+
+            default.with_file_name(format!("{}.pid", name.replace('/', "_")))
+        }
+    }
+}
+
+/// Default log file used when the daemon runs in the background.
+#[must_use]
+pub fn log_file(socket: &SocketAddress) -> PathBuf {
+    pid_file(socket).with_extension("log")
+}
+
+fn read_pid(file: &mut File) -> Option<i32> {
+    let mut contents = String::new();
+    file.read_to_string(&mut contents).ok()?;
+
+    contents.trim().parse().ok()
+}
+
+fn create_parent(path: &Path) -> Result<()> {
+    if let Some(parent) = path.parent().filter(|parent| !parent.exists()) {
+        debug!("Creating directory {}", parent.display());
+        DirBuilder::new()
+            .recursive(true)
+            .mode(0o700)
+            .create(parent)?;
+    }
+
+    // The directory may have existed before, created by someone else
+    discovery::check_socket_dir(path)?;
+
+    Ok(())
+}
@@ -0,0 +1,342 @@
+use crate::{
+    config::configuration::Configuration,
+    error::{Error, Result},
+    helper::{Access, Helper},
+    logger, oauth,
+};
+use ebay_authd_client::Client;
+use ebay_authd_core::{response::Response, secret::Secret, state::State};
+use log::{debug, error, info, warn, LevelFilter};
+use std::time::{Duration, Instant};
+
+/// Delay before retrying the first failed refresh.
+const MIN_BACKOFF: Duration = Duration::from_secs(5);
+/// Upper bound for the delay between failed refreshes.
+const MAX_BACKOFF: Duration = Duration::from_secs(300);
+/// Longest time a client can wait for a token, longer requests are shortened.
+const MAX_WAIT: Duration = Duration::from_secs(3600);
+/// Number of clients that can wait for a token at the same time.
+const MAX_WAITING: usize = 64;
+
+/// A client waiting for a token to become available.
+struct Waiter {
+    client: Client,
+    deadline: Instant,
+}
+
+/// Daemon state shared by all client requests.
+///
+/// The grant itself is held by the refresh helper, the daemon only keeps the
+/// current access token.
+pub struct Daemon {
+    helper: Helper,
+    /// Scopes requested by new authorizations
+    scopes: Box<[Box<str>]>,
+    access: Option<Access>,
+    /// Deadline of the authorization pending in the helper
+    authorization: Option<Instant>,
+    state: State,
+    /// State to return to if a pending authorization fails
+    fallback: State,
+    /// Time of the next refresh attempt and current backoff after a failure
+    retry: Option<(Instant, Duration)>,
+    waiting: Vec<Waiter>,
+}
+
+impl Daemon {
+    #[must_use]
+    pub fn new(helper: Helper, scopes: Box<[Box<str>]>, access: Option<Access>) -> Self {
+        let state = if access.is_some() {
+            State::Ready
+        } else {
+            State::Unauthenticated
+        };
+
+        Self {
+            helper,
+            scopes,
+            access,
+            authorization: None,
+            state,
+            fallback: state,
+            retry: None,
+            waiting: Vec::new(),
+        }
+    }
+
+    /// Load the configuration through the helper, which applies new client
+    /// credentials itself. The Cert ID of the result is empty.
+    pub fn load_config(&mut self) -> Result<Configuration> {
+        self.helper.reload()
+    }
+
+    /// Change the log level of this process and the refresh helper.
+    pub fn set_log_level(&mut self, level: LevelFilter) -> Result<()> {
+        logger::set_level(level);
+        self.helper.set_log_level(level)
+    }
+
+    /// Request `scopes` in future authorizations.
+    pub fn set_scopes(&mut self, scopes: Box<[Box<str>]>) {
+        self.scopes = scopes;
+    }
+
+    /// Whether the refresh helper is gone, which leaves the daemon unable to
+    /// ever serve a token again.
+    #[must_use]
+    pub fn helper_exited(&self) -> bool {
+        self.helper.exited()
+    }
+
+    #[must_use]
+    pub fn state(&self) -> State {
+        self.state
+    }
+
+    #[must_use]
+    pub fn access(&self) -> Option<&Access> {
+        self.access.as_ref()
+    }
+
+    /// The current access token, if one can be handed out in this state.
+    #[must_use]
+    pub fn token(&self) -> Option<Secret> {
+        if !matches!(
+            self.state,
+            State::Ready | State::Refreshing | State::Degraded
+        ) {
+            return None;
+        }
+
+        self.access
+            .as_ref()
+            .filter(|access| !access.is_expired())
+            .map(Access::get_token)
+    }
+
+    /// Answer a token request, either immediately or once a token is
+    /// available, waiting at most `timeout` (up to [`MAX_WAIT`]).
+    pub fn request_token(&mut self, mut client: Client, timeout: Duration) -> Result<()> {
+        if let Some(token) = self.token() {
+            return Ok(client.message(Response::Token(token))?);
+        }
+
+        if timeout.is_zero() {
+            return Ok(client.message(Response::NotReady(self.state))?);
+        }
+
+        if self.waiting.len() >= MAX_WAITING {
+            warn!("Too many clients waiting for a token, refusing another one");
+            return Ok(client.message(Response::Error(
+                "too many clients waiting for a token".into(),
+            ))?);
+        }
+
+        let timeout = timeout.min(MAX_WAIT);
+        debug!("Client waiting up to {}s for a token", timeout.as_secs());
+        self.waiting.push(Waiter {
+            client,
+            deadline: Instant::now() + timeout,
+        });
+
+        Ok(())
+    }
+
+    /// Refresh the token when it is about to expire or a retry is due.
+    pub fn tick(&mut self) {
+        self.expire_authorization();
+
+        let Some(access) = &self.access else {
+            self.serve_waiting();
+            return;
+        };
+
+        let due = match self.retry {
+            Some((at, _)) => Instant::now() >= at,
+            None => access.expires_soon(),
+        };
+
+        if due {
+            // Failures are handled by changing state
+            let _ = self.refresh();
+        }
+
+        self.serve_waiting();
+    }
+
+    pub fn refresh(&mut self) -> Result<()> {
+        if self.access.is_none() {
+            return Err(Error::NotAuthenticated);
+        }
+
+        self.set_state(State::Refreshing);
+        let result = self.helper.refresh().map(|access| {
+            self.access = Some(access);
+        });
+
+        match &result {
+            Ok(()) => {
+                self.retry = None;
+                self.set_state(State::Ready);
+            }
+            Err(Error::HelperExited) => {
+                error!("Refresh helper exited, the daemon can't get tokens anymore");
+            }
+            Err(Error::GrantRejected) => {
+                error!("Refresh token rejected, run `ebay_authd auth login` to reauthorize");
+                self.access = None;
+                self.retry = None;
+                self.set_state(State::ReauthRequired);
+            }
+            Err(why) => {
+                let backoff = self
+                    .retry
+                    .map_or(MIN_BACKOFF, |(_, backoff)| (backoff * 2).min(MAX_BACKOFF));
+
+                warn!(
+                    "Failed to refresh token: {why}, retrying in {}s",
+                    backoff.as_secs()
+                );
+                self.retry = Some((Instant::now() + backoff, backoff));
+                self.set_state(State::Degraded);
+            }
+        }
+
+        self.serve_waiting();
+        result
+    }
+
+    /// Start a new authorization and return the consent URL.
+    pub fn begin_auth(&mut self) -> Result<Box<str>> {
+        let url = self.helper.begin_auth(&self.scopes)?;
+        self.authorization = Some(Instant::now() + oauth::CODE_LIFETIME);
+
+        if self.access.is_none() && self.state != State::Authorizing {
+            self.fallback = self.state;
+            self.set_state(State::Authorizing);
+        }
+
+        Ok(url)
+    }
+
+    /// Whether an authorization is waiting to be completed.
+    #[must_use]
+    pub fn auth_pending(&self) -> bool {
+        self.authorization.is_some()
+    }
+
+    /// Finish the pending authorization using the redirect URL or code.
+    ///
+    /// The authorization stays pending if the input was malformed, so the
+    /// client can try again with the same consent URL.
+    pub fn complete_auth(&mut self, input: Secret) -> Result<()> {
+        let deadline = self
+            .authorization
+            .take()
+            .ok_or(Error::NoAuthorizationPending)?;
+
+        let result = if Instant::now() >= deadline {
+            Err(Error::AuthorizationExpired)
+        } else {
+            match self.helper.complete_auth(input) {
+                Err(why @ (Error::MissingCode | Error::StateMismatch)) => {
+                    self.authorization = Some(deadline);
+                    return Err(why);
+                }
+                result => result,
+            }
+        };
+
+        match result {
+            Ok(access) => {
+                self.access = Some(access);
+                self.retry = None;
+                self.set_state(State::Ready);
+                self.serve_waiting();
+
+                Ok(())
+            }
+            Err(why) => {
+                // Keep using the current grant, if any
+                if self.state == State::Authorizing {
+                    self.set_state(self.fallback);
+                }
+
+                Err(why)
+            }
+        }
+    }
+
+    /// Forget the grant and revoke it at the provider.
+    ///
+    /// The daemon requires a new authorization afterwards, even if revocation
+    /// fails. Returns whether there was a grant that was revoked.
+    pub fn revoke(&mut self) -> Result<bool> {
+        self.authorization = None;
+        self.retry = None;
+        self.access = None;
+
+        self.set_state(State::ReauthRequired);
+        self.serve_waiting();
+
+        self.helper.revoke()
+    }
+
+    /// Abandon a pending authorization once its consent can no longer be
+    /// completed.
+    fn expire_authorization(&mut self) {
+        if self
+            .authorization
+            .is_none_or(|deadline| Instant::now() < deadline)
+        {
+            return;
+        }
+
+        info!("Pending authorization expired");
+        self.authorization = None;
+
+        if self.state == State::Authorizing {
+            self.set_state(self.fallback);
+        }
+    }
+
+    /// Enter the stopping state and release all waiting clients.
+    pub fn stop(&mut self) {
+        self.set_state(State::Stopping);
+
+        for mut waiter in self.waiting.drain(..) {
+            let _ = waiter.client.message(Response::NotReady(State::Stopping));
+        }
+    }
+
+    fn set_state(&mut self, state: State) {
+        if self.state != state {
+            info!("State: {} -> {state}", self.state);
+            self.state = state;
+        }
+    }
+
+    fn serve_waiting(&mut self) {
+        if self.waiting.is_empty() {
+            return;
+        }
+
+        let token = self.token();
+        let now = Instant::now();
+        let state = self.state;
+
+        self.waiting.retain_mut(|waiter| {
+            let response = match &token {
+                Some(token) => Response::Token(token.clone()),
+                None if now >= waiter.deadline => Response::NotReady(state),
+                None => return true,
+            };
+
+            if let Err(why) = waiter.client.message(response) {
+                warn!("Failed to answer waiting client: {why}");
+            }
+
+            false
+        });
+    }
+}
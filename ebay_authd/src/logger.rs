@@ -0,0 +1,555 @@
+use crate::{error::Result, memory};
+use clap::ValueEnum;
+use colored::Colorize;
+use ebay_authd_core::secret::{Secret, REDACTED};
+use log::{Level, LevelFilter, Log, Metadata, Record};
+use nix::unistd::dup2;
+use serde_json::json;
+use std::{
+    collections::VecDeque,
+    fs::{self, File, OpenOptions},
+    io::{self, IsTerminal, Write},
+    os::{
+        fd::AsRawFd,
+        unix::fs::{MetadataExt, OpenOptionsExt},
+        unix::net::UnixDatagram,
+    },
+    path::{Path, PathBuf},
+    sync::{Mutex, MutexGuard, OnceLock},
+    time::SystemTime,
+};
+
+/// Modules that are too noisy to log.
+const SILENCED: [&str; 2] = ["reqwest", "rustls"];
+/// Socket of the systemd journal's native protocol.
+const JOURNALD_SOCKET: &str = "/run/systemd/journal/socket";
+const IDENTIFIER: &str = "ebay_authd";
+/// Number of short-lived secrets to mask, older ones are forgotten.
+const MAX_SHORT_LIVED: usize = 16;
+/// Prefix of eBay's OAuth tokens.
+const TOKEN_PREFIX: &str = "v^1.1#";
+/// Query parameter carrying an authorization code.
+const CODE_PARAM: &str = "code=";
+
+static LOGGER: OnceLock<Logger> = OnceLock::new();
+static MASKER: Mutex<Masker> = Mutex::new(Masker::new());
+
+/// Masks secrets in log messages.
+///
+/// Registered secrets are zeroed when forgotten, like all other copies of
+/// secrets.
+struct Masker {
+    /// Masked as long as the process runs, e.g. the Cert ID, and kept in
+    /// locked memory
+    long_lived: Vec<Secret>,
+    /// The most recent access tokens and authorization codes
+    short_lived: VecDeque<Secret>,
+}
+
+impl Masker {
+    const fn new() -> Self {
+        Self {
+            long_lived: Vec::new(),
+            short_lived: VecDeque::new(),
+        }
+    }
+
+    fn iter(&self) -> impl Iterator<Item = &str> {
+        self.long_lived
+            .iter()
+            .chain(&self.short_lived)
+            .map(Secret::expose)
+    }
+
+    /// Whether `secret` is not masked yet and long enough to be masked
+    /// without garbling unrelated text.
+    fn is_new(&self, secret: &str) -> bool {
+        secret.len() >= 8 && !self.iter().any(|known| known == secret)
+    }
+
+    fn add_long_lived(&mut self, secret: Secret) {
+        if self.is_new(secret.expose()) {
+            self.long_lived.push(secret);
+        }
+    }
+
+    fn add_short_lived(&mut self, secret: &str) {
+        if !self.is_new(secret) {
+            return;
+        }
+
+        if self.short_lived.len() == MAX_SHORT_LIVED {
+            // Dropping zeroes it
+            self.short_lived.pop_front();
+        }
+        self.short_lived.push_back(secret.into());
+    }
+
+    /// Replace registered secrets, eBay tokens and authorization codes in
+    /// `message`.
+    fn mask(&self, mut message: String) -> String {
+        for secret in self.iter() {
+            if message.contains(secret) {
+                message = message.replace(secret, REDACTED);
+            }
+        }
+
+        let message = mask_after(&message, TOKEN_PREFIX, |c| {
+            c.is_whitespace() || matches!(c, '"' | '\'' | ')')
+        });
+        mask_after(&message, CODE_PARAM, |c| {
+            c.is_whitespace() || matches!(c, '&' | '"' | '\'' | ')')
+        })
+    }
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum LogFormat {
+    /// Human readable lines
+    #[default]
+    Text,
+    /// One JSON object per line
+    Json,
+}
+
+/// Where log records go.
+pub enum Target {
+    Stderr,
+    File(RotatingFile),
+    Journald(UnixDatagram),
+}
+
+struct Logger {
+    format: LogFormat,
+    target: Mutex<Target>,
+    masker: &'static Mutex<Masker>,
+}
+
+/// Level used unless configured otherwise.
+#[must_use]
+pub fn default_level() -> LevelFilter {
+    if cfg!(debug_assertions) {
+        LevelFilter::Debug
+    } else {
+        LevelFilter::Info
+    }
+}
+
+/// Install the logger.
+pub fn init(level: LevelFilter, format: LogFormat, target: Target) {
+    let logger = LOGGER.get_or_init(|| Logger {
+        format,
+        target: Mutex::new(target),
+        masker: &MASKER,
+    });
+
+    log::set_logger(logger).expect("logger is only initialized once");
+    log::set_max_level(level);
+}
+
+/// Change the log level at runtime.
+pub fn set_level(level: LevelFilter) {
+    let current = log::max_level();
+    if current != level {
+        // Report the change even when lowering the level
+        log::set_max_level(current.max(level).max(LevelFilter::Info));
+        log::info!("Log level set to {level}");
+        log::set_max_level(level);
+    }
+}
+
+/// Log to `file` from now on, e.g. after moving into the background. Logging
+/// to the journal is kept.
+pub fn log_to_file(file: RotatingFile) {
+    if let Some(logger) = LOGGER.get() {
+        let mut target = logger.target.lock().unwrap_or_else(|e| e.into_inner());
+        if !matches!(*target, Target::Journald(_)) {
+            *target = Target::File(file);
+        }
+    }
+}
+
+/// Mask the long-lived `secret`, e.g. the Cert ID or a refresh token, in
+/// all further log lines.
+pub fn register_secret(secret: &str) {
+    if !lock_masker(&MASKER).is_new(secret) {
+        return;
+    }
+
+    // Locked before taking the lock on the masker, as failures are logged
+    let secret = Secret::new(secret);
+    memory::lock(secret.expose());
+
+    lock_masker(&MASKER).add_long_lived(secret);
+}
+
+/// Mask the short-lived `secret`, e.g. an access token or authorization code,
+/// until [`MAX_SHORT_LIVED`] newer ones were registered.
+pub fn register_token(secret: &str) {
+    lock_masker(&MASKER).add_short_lived(secret);
+}
+
+fn lock_masker(masker: &Mutex<Masker>) -> MutexGuard<'_, Masker> {
+    masker.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+/// Replace what follows each `marker` in `message` up to `end`.
+fn mask_after(message: &str, marker: &str, end: impl Fn(char) -> bool) -> String {
+    let mut masked = String::with_capacity(message.len());
+    let mut rest = message;
+
+    while let Some(index) = rest.find(marker) {
+        let (before, after) = rest.split_at(index + marker.len());
+        masked.push_str(before);
+
+        let secret_len = after.find(&end).unwrap_or(after.len());
+        if secret_len > 0 {
+            masked.push_str(REDACTED);
+        }
+        rest = &after[secret_len..];
+    }
+
+    masked.push_str(rest);
+    masked
+}
+
+impl Target {
+    /// Connect to the systemd journal.
+    pub fn journald() -> Result<Self> {
+        let socket = UnixDatagram::unbound()?;
+        socket.connect(JOURNALD_SOCKET)?;
+
+        Ok(Self::Journald(socket))
+    }
+}
+
+impl Log for Logger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.level() <= log::max_level()
+            && !SILENCED
+                .iter()
+                .any(|module| metadata.target().starts_with(module))
+    }
+
+    fn log(&self, record: &Record) {
+        if !self.enabled(record.metadata()) {
+            return;
+        }
+
+        let message = lock_masker(self.masker).mask(record.args().to_string());
+        let mut target = self.target.lock().unwrap_or_else(|e| e.into_inner());
+
+        // Nowhere left to report logging failures
+        let _ = match &mut *target {
+            Target::Stderr => {
+                let colored = io::stderr().is_terminal();
+                writeln!(io::stderr(), "{}", self.format(record, &message, colored))
+            }
+            Target::File(file) => file.write_line(&self.format(record, &message, false)),
+            Target::Journald(socket) => socket.send(&journald_entry(record, message)).map(|_| ()),
+        };
+    }
+
+    fn flush(&self) {
+        if let Target::File(file) = &mut *self.target.lock().unwrap_or_else(|e| e.into_inner()) {
+            let _ = file.file.flush();
+        }
+    }
+}
+
+impl Logger {
+    fn format(&self, record: &Record, message: &str, colored: bool) -> String {
+        let timestamp = humantime::format_rfc3339_millis(SystemTime::now());
+
+        match self.format {
+            LogFormat::Text => {
+                let level = format!("{:<5}", record.level());
+                let level = if colored {
+                    match record.level() {
+                        Level::Error => level.red().to_string(),
+                        Level::Warn => level.yellow().to_string(),
+                        Level::Info => level.cyan().to_string(),
+                        Level::Debug => level.purple().to_string(),
+                        Level::Trace => level.normal().to_string(),
+                    }
+                } else {
+                    level
+                };
+
+                format!("{timestamp} {level} [{}] {message}", record.target())
+            }
+            LogFormat::Json => json!({
+                "timestamp": timestamp.to_string(),
+                "level": record.level().as_str(),
+                "target": record.target(),
+                "message": message,
+            })
+            .to_string(),
+        }
+    }
+}
+
+/// Encode `record` with its masked `message` in the journal's native
+/// protocol.
+fn journald_entry(record: &Record, message: String) -> Vec<u8> {
+    let priority = match record.level() {
+        Level::Error => "3",
+        Level::Warn => "4",
+        Level::Info => "6",
+        Level::Debug | Level::Trace => "7",
+    };
+
+    let mut entry = Vec::new();
+    for (name, value) in [
+        ("PRIORITY", priority.to_string()),
+        ("SYSLOG_IDENTIFIER", IDENTIFIER.to_string()),
+        ("TARGET", record.target().to_string()),
+        ("MESSAGE", message),
+    ] {
+        entry.extend_from_slice(name.as_bytes());
+
+        if value.contains('\n') {
+            // Values with newlines are sent length prefixed
+            entry.push(b'\n');
+            entry.extend_from_slice(&(value.len() as u64).to_le_bytes());
+        } else {
+            entry.push(b'=');
+        }
+
+        entry.extend_from_slice(value.as_bytes());
+        entry.push(b'\n');
+    }
+
+    entry
+}
+
+/// A log file that is rotated once it grows past a maximum size.
+///
+/// Rotated files get a numbered suffix (`.1` is the newest), at most `keep`
+/// of them are kept. The socket server and the refresh helper share the file,
+/// whichever of them finds it full rotates it and the other one reopens it.
+pub struct RotatingFile {
+    path: PathBuf,
+    file: File,
+    max_size: u64,
+    keep: usize,
+    /// Point standard output and error at the new file after rotating
+    redirect_stdio: bool,
+}
+
+impl RotatingFile {
+    pub fn open(path: &Path, max_size: u64, keep: usize) -> Result<Self> {
+        Ok(Self {
+            path: path.into(),
+            file: open(path)?,
+            max_size,
+            keep,
+            redirect_stdio: false,
+        })
+    }
+
+    /// Also redirect standard output and error to the file, e.g. when
+    /// running in the background.
+    pub fn redirect_stdio(mut self) -> Result<Self> {
+        self.redirect_stdio = true;
+        self.redirect()?;
+
+        Ok(self)
+    }
+
+    fn write_line(&mut self, line: &str) -> io::Result<()> {
+        let size = self.file.metadata()?.len();
+        if size > 0 && size + line.len() as u64 >= self.max_size {
+            if self.is_current() {
+                self.rotate()?;
+            } else {
+                self.reopen()?;
+            }
+        }
+
+        writeln!(self.file, "{line}")
+    }
+
+    /// Whether the open file is still the one at the path, i.e. it was not
+    /// rotated by the other process.
+    fn is_current(&self) -> bool {
+        match (fs::metadata(&self.path), self.file.metadata()) {
+            (Ok(path), Ok(file)) => path.dev() == file.dev() && path.ino() == file.ino(),
+            _ => false,
+        }
+    }
+
+    fn rotate(&mut self) -> io::Result<()> {
+        for index in (1..self.keep).rev() {
+            let from = numbered(&self.path, index);
+            if from.exists() {
+                fs::rename(from, numbered(&self.path, index + 1))?;
+            }
+        }
+
+        if self.keep > 0 {
+            fs::rename(&self.path, numbered(&self.path, 1))?;
+        } else {
+            fs::remove_file(&self.path)?;
+        }
+
+        self.reopen()
+    }
+
+    fn reopen(&mut self) -> io::Result<()> {
+        self.file = open(&self.path)?;
+
+        if self.redirect_stdio {
+            self.redirect()?;
+        }
+
+        Ok(())
+    }
+
+    fn redirect(&self) -> io::Result<()> {
+        dup2(self.file.as_raw_fd(), 1)?;
+        dup2(self.file.as_raw_fd(), 2)?;
+
+        Ok(())
+    }
+}
+
+fn open(path: &Path) -> io::Result<File> {
+    OpenOptions::new()
+        .create(true)
+        .append(true)
+        .mode(0o600)
+        .open(path)
+}
+
+fn numbered(path: &Path, index: usize) -> PathBuf {
+    let mut path = path.as_os_str().to_owned();
+    path.push(format!(".{index}"));
+    path.into()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{lock_masker, LogFormat, Logger, Masker, RotatingFile, Target, MAX_SHORT_LIVED};
+    use log::{Level, LevelFilter, Log, Record};
+    use std::{
+        env, fs,
+        os::unix::net::UnixDatagram,
+        path::{Path, PathBuf},
+        process,
+        sync::Mutex,
+    };
+
+    const CERTID: &str = "PRD-0123456789ab-cdef-0123-4567-89ab";
+    const ACCESS_TOKEN: &str = "access-token-0123456789";
+    /// Unregistered token, recognized by its prefix
+    const TOKEN: &str = "v^1.1#i^1#p^3#r^1#I^3#f^0#t^Ul4xMF8=";
+    const CODE: &str = "v%5E1.1%23i%5E1%23f%5E0";
+
+    fn message() -> String {
+        format!(
+            "certid {CERTID}, token {ACCESS_TOKEN}, {TOKEN} and \
+             https://signin.ebay.com/?state=abc&code={CODE}&expires_in=299"
+        )
+    }
+
+    /// A logger with its own secrets, independent of other tests.
+    fn logger(format: LogFormat, target: Target) -> Logger {
+        Logger {
+            format,
+            target: Mutex::new(target),
+            masker: Box::leak(Box::new(Mutex::new(Masker::new()))),
+        }
+    }
+
+    fn log(logger: &Logger, message: &str) {
+        log::set_max_level(LevelFilter::Trace);
+        let mut masker = lock_masker(logger.masker);
+        masker.add_long_lived(CERTID.into());
+        masker.add_short_lived(ACCESS_TOKEN);
+        drop(masker);
+
+        logger.log(
+            &Record::builder()
+                .args(format_args!("{message}"))
+                .level(Level::Info)
+                .target("ebay_authd::test")
+                .build(),
+        );
+        logger.flush();
+    }
+
+    fn assert_masked(line: &str) {
+        for secret in [CERTID, ACCESS_TOKEN, "i^1#p^3", CODE] {
+            assert!(!line.contains(secret), "{secret} in {line}");
+        }
+        assert_eq!(line.matches("<redacted>").count(), 4, "{line}");
+    }
+
+    fn log_file(name: &str) -> PathBuf {
+        let path = env::temp_dir().join(format!("ebay_authd-test-{}-{name}.log", process::id()));
+        let _ = fs::remove_file(&path);
+        path
+    }
+
+    fn file_logger(format: LogFormat, path: &Path) -> Logger {
+        let file = RotatingFile::open(path, 1 << 20, 0).unwrap();
+        logger(format, Target::File(file))
+    }
+
+    #[test]
+    fn text_is_masked() {
+        let path = log_file("text");
+        log(&file_logger(LogFormat::Text, &path), &message());
+
+        let contents = fs::read_to_string(&path).unwrap();
+        assert!(contents.contains("[ebay_authd::test] certid <redacted>"));
+        assert_masked(&contents);
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn json_is_masked() {
+        let path = log_file("json");
+        log(&file_logger(LogFormat::Json, &path), &message());
+
+        let contents = fs::read_to_string(&path).unwrap();
+        let entry: serde_json::Value = serde_json::from_str(&contents).unwrap();
+        assert_masked(entry["message"].as_str().unwrap());
+        assert_masked(&contents);
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn journald_is_masked() {
+        let (journal, receiver) = UnixDatagram::pair().unwrap();
+        log(
+            &logger(LogFormat::Text, Target::Journald(journal)),
+            &message(),
+        );
+
+        let mut entry = vec![0; 4096];
+        let len = receiver.recv(&mut entry).unwrap();
+        let entry = String::from_utf8_lossy(&entry[..len]);
+        assert!(entry.contains("SYSLOG_IDENTIFIER=ebay_authd\n"));
+        assert_masked(&entry);
+    }
+
+    #[test]
+    fn long_lived_secrets_are_kept() {
+        let mut masker = Masker::new();
+        masker.add_long_lived(CERTID.into());
+        for index in 0..=MAX_SHORT_LIVED * 2 {
+            masker.add_short_lived(&format!("refreshed-access-token-{index:04}"));
+        }
+
+        let masked = masker.mask(format!("certid {CERTID}"));
+        assert_eq!(masked, "certid <redacted>");
+
+        let masked = masker.mask("old refreshed-access-token-0000".into());
+        assert_eq!(masked, "old refreshed-access-token-0000");
+
+        let newest = format!("refreshed-access-token-{:04}", MAX_SHORT_LIVED * 2);
+        assert_eq!(masker.mask(newest), "<redacted>");
+    }
+}
@@ -6,51 +6,119 @@
 
 use crate::error::Result;
 use clap::Parser;
-use cli::{Cli, DaemonCommand, TestCommand};
-use config::configuration::Configuration;
-use log::{debug, info, LevelFilter};
-use simple_logger::SimpleLogger;
-use std::process::exit;
+use cli::{AuthCommand, Cli, Command, ConfigCommand, DaemonCommand, TestCommand};
+use log::debug;
+use logger::{RotatingFile, Target};
 
 mod cli;
 mod commands;
 mod config;
+mod consent;
+mod credentials;
+mod daemonize;
 mod error;
+mod helper;
+mod instance;
+mod lifecycle;
+mod logger;
+mod memory;
+mod multiplexer;
+mod oauth;
+mod qr;
+mod redirect;
+mod reload;
+mod sandbox;
+mod signals;
+mod systemd;
 pub mod tokenmgr;
+mod user;
+mod watch;
 
 fn main() -> Result<()> {
-    let mut logger = SimpleLogger::new()
-        .with_module_level("reqwest", LevelFilter::Off)
-        .with_module_level("rustls", LevelFilter::Off)
-        .with_level(LevelFilter::Info);
+    let cli = Cli::parse();
 
-    if cfg!(debug_assertions) {
-        logger = logger.with_level(LevelFilter::Debug);
-    }
+    let target = if cli.log.journald {
+        Target::journald()?
+    } else if let Some(path) = &cli.log.log_file {
+        Target::File(RotatingFile::open(
+            path,
+            cli.log.log_max_size,
+            cli.log.log_keep,
+        )?)
+    } else {
+        Target::Stderr
+    };
+    logger::init(
+        cli.log.log_level.unwrap_or_else(logger::default_level),
+        cli.log.log_format,
+        target,
+    );
 
-    logger.init().unwrap();
+    let location = config::location(cli.config.as_deref());
 
-    let cli = Cli::parse();
+    // Handled before loading, as the configuration may not exist or be valid
+    if let Command::Config { command } = &cli.command {
+        return match command {
+            ConfigCommand::Init { force } => commands::config::init(location.as_deref(), *force),
+            ConfigCommand::Validate => commands::config::validate(location.as_deref()),
+            ConfigCommand::Show => commands::config::show(location.as_deref()),
+            ConfigCommand::Migrate { write } => {
+                commands::config::migrate(location.as_deref(), *write)
+            }
+        };
+    }
+
+    // Secrets are only resolved by the commands that use them
+    let config = config::load_unresolved(location.as_deref())?;
 
-    if !config::config_exists()? {
-        info!("Creating default empty configuration");
-        config::create_config()?;
-        exit(0);
+    if let (None, Some(level)) = (cli.log.log_level, config.log_level) {
+        log::set_max_level(level);
     }
 
-    debug!("Loading configuration");
-    let config: Configuration = confy::load_path(config::location()?)?;
+    let socket = config::socket_address(&config, cli.socket.as_deref())?;
+    debug!("Using socket {socket}");
 
-    match cli {
-        Cli::Daemon { command } => match command {
-            DaemonCommand::Start { screen } => commands::daemon::start(&config, screen)?,
-            DaemonCommand::Reauth => commands::testcmds::reauth()?,
-            DaemonCommand::Stop => commands::testcmds::stop()?,
-            DaemonCommand::Status => commands::testcmds::status(),
+    match cli.command {
+        Command::Daemon { command } => match command {
+            DaemonCommand::Start(args) => {
+                commands::daemon::start(config, location.as_deref(), &socket, args, &cli.log)?;
+            }
+            DaemonCommand::Reload => commands::testcmds::reload(&socket)?,
+            DaemonCommand::Reauth(args) => commands::auth::login(&socket, args.open, &args.qr)?,
+            DaemonCommand::LogLevel { level } => commands::testcmds::log_level(&socket, level)?,
+            DaemonCommand::Refresh => commands::testcmds::refresh(&socket)?,
+            DaemonCommand::Stop => commands::testcmds::stop(&socket)?,
+            DaemonCommand::Status => commands::testcmds::status(&socket),
+        },
+        Command::Test { command } => match command {
+            TestCommand::Token { wait } => commands::testcmds::token(&socket, wait)?,
         },
-        Cli::Test { command } => match command {
-            TestCommand::Token => commands::testcmds::token()?,
+        Command::Auth { command } => match command {
+            AuthCommand::Login(args) => match &args.output {
+                Some(output) => commands::auth::login_standalone(
+                    &config::resolve_secrets(config)?,
+                    output,
+                    &args,
+                )?,
+                None => commands::auth::login(&socket, args.open, &args.qr)?,
+            },
+            AuthCommand::Mint { refresh_token_file } => {
+                commands::auth::mint(
+                    &config::resolve_secrets(config)?,
+                    refresh_token_file.as_deref(),
+                )?;
+            }
+            AuthCommand::Logout { refresh_token_file } => match refresh_token_file {
+                Some(path) => {
+                    commands::auth::logout_standalone(&config::resolve_secrets(config)?, &path)?;
+                }
+                None => commands::auth::logout(&socket)?,
+            },
+            AuthCommand::Inspect { refresh_token_file } => {
+                commands::auth::inspect(&refresh_token_file)?;
+            }
         },
+        Command::Config { .. } => unreachable!("handled above"),
     }
 
     Ok(())
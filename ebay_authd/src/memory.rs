@@ -0,0 +1,57 @@
+use log::{debug, warn};
+use nix::sys::{
+    mman::{mlock, munlock},
+    resource::{setrlimit, Resource},
+};
+use std::{ffi::c_void, ptr::NonNull};
+
+/// Keep secrets out of core dumps.
+///
+/// Disables core dumps for the process and, on Linux, marks it as not
+/// dumpable, which also keeps other processes of the same user from
+/// attaching to it or reading its memory.
+pub fn harden() {
+    if let Err(why) = setrlimit(Resource::RLIMIT_CORE, 0, 0) {
+        warn!("Failed to disable core dumps: {why}");
+    }
+
+    #[cfg(target_os = "linux")]
+    if let Err(why) = nix::sys::prctl::set_dumpable(false) {
+        warn!("Failed to mark process as not dumpable: {why}");
+    }
+}
+
+/// Keep the pages holding `secret` from being swapped out.
+///
+/// Fails with a warning, e.g. if `RLIMIT_MEMLOCK` is exhausted.
+pub fn lock(secret: &str) {
+    let Some(address) = address(secret) else {
+        return;
+    };
+
+    // SAFETY: the range is the allocation backing `secret`
+    match unsafe { mlock(address, secret.len()) } {
+        Ok(()) => debug!("Locked {} bytes of secret memory", secret.len()),
+        Err(why) => warn!("Failed to lock secret memory: {why}"),
+    }
+}
+
+/// Allow the pages holding `secret` to be swapped out again.
+pub fn unlock(secret: &str) {
+    let Some(address) = address(secret) else {
+        return;
+    };
+
+    // SAFETY: see `lock`
+    if let Err(why) = unsafe { munlock(address, secret.len()) } {
+        debug!("Failed to unlock secret memory: {why}");
+    }
+}
+
+fn address(secret: &str) -> Option<NonNull<c_void>> {
+    if secret.is_empty() {
+        return None;
+    }
+
+    NonNull::new(secret.as_ptr().cast_mut().cast())
+}
@@ -0,0 +1,77 @@
+use crate::error::{Error, Result};
+use clap::ValueEnum;
+use log::{debug, error};
+use serde::{Deserialize, Serialize};
+use std::process::Command;
+
+pub mod screen;
+pub mod tmux;
+
+/// Session name used for the daemon.
+const SESSION: &str = "ebay_authd";
+
+/// A terminal multiplexer that can host the daemon.
+pub trait Multiplexer {
+    /// Name of the multiplexer binary.
+    fn name(&self) -> &'static str;
+
+    /// Whether the current process is running inside a session.
+    fn running_inside(&self) -> bool;
+
+    /// Re-run the current command inside a new session and attach to it.
+    ///
+    /// This does not return, the process exits once the session is detached.
+    fn restart_inside(&self) -> Result<()>;
+
+    /// Detach the daemon's session from its terminal.
+    fn detach(&self) -> Result<()>;
+
+    /// Check that the multiplexer is installed.
+    fn check(&self) -> Result<()> {
+        let name = self.name();
+
+        match Command::new(name).arg(self.version_flag()).output() {
+            Ok(output) if output.status.success() => {
+                let version_string = String::from_utf8(output.stdout)?;
+                debug!("Found {name}: {version_string}");
+
+                Ok(())
+            }
+            Ok(output) => {
+                error!(
+                    "{name} found but got unexpected status code: {}",
+                    output.status
+                );
+                Err(Error::Multiplexer(name))
+            }
+            Err(why) => {
+                error!("{name} not installed or not in PATH: {why}");
+                Err(Error::Multiplexer(name))
+            }
+        }
+    }
+
+    /// Flag that makes the multiplexer print its version.
+    fn version_flag(&self) -> &'static str {
+        "--version"
+    }
+}
+
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize, ValueEnum,
+)]
+#[serde(rename_all = "lowercase")]
+pub enum MultiplexerKind {
+    Screen,
+    Tmux,
+}
+
+impl MultiplexerKind {
+    #[must_use]
+    pub fn multiplexer(self) -> Box<dyn Multiplexer> {
+        match self {
+            Self::Screen => Box::new(screen::Screen),
+            Self::Tmux => Box::new(tmux::Tmux),
+        }
+    }
+}
@@ -0,0 +1,43 @@
+use super::{Multiplexer, SESSION};
+use crate::error::Result;
+use std::{env, process::exit, process::Command, thread::sleep, time::Duration};
+
+/// GNU screen
+pub struct Screen;
+
+impl Multiplexer for Screen {
+    fn name(&self) -> &'static str {
+        "screen"
+    }
+
+    fn running_inside(&self) -> bool {
+        env::var("STY").is_ok_and(|value| !value.is_empty())
+    }
+
+    fn restart_inside(&self) -> Result<()> {
+        let args: Vec<String> = env::args().collect();
+
+        // Create a new session
+        Command::new("screen")
+            .arg("-dmS")
+            .arg(SESSION)
+            .args(args)
+            .output()?;
+
+        sleep(Duration::from_secs(2));
+
+        // Attach to the new session
+        Command::new("screen")
+            .arg("-r")
+            .arg(SESSION)
+            .spawn()?
+            .wait()?;
+
+        exit(0);
+    }
+
+    fn detach(&self) -> Result<()> {
+        Command::new("screen").arg("-d").arg(SESSION).spawn()?;
+        Ok(())
+    }
+}
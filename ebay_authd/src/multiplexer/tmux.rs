@@ -0,0 +1,75 @@
+use super::{Multiplexer, SESSION};
+use crate::{
+    config::{overrides, secret},
+    error::{Error, Result},
+};
+use std::{env, process::exit, process::Command};
+
+/// Variables besides the `EBAY_AUTHD_` ones that decide which configuration,
+/// socket and secrets the daemon uses.
+const FORWARDED: [&str; 2] = ["XDG_RUNTIME_DIR", secret::CREDENTIALS_DIRECTORY_ENV];
+
+/// tmux
+pub struct Tmux;
+
+impl Multiplexer for Tmux {
+    fn name(&self) -> &'static str {
+        "tmux"
+    }
+
+    fn version_flag(&self) -> &'static str {
+        "-V"
+    }
+
+    fn running_inside(&self) -> bool {
+        env::var("TMUX").is_ok_and(|value| !value.is_empty())
+    }
+
+    fn restart_inside(&self) -> Result<()> {
+        let args: Vec<String> = env::args().collect();
+
+        // A running tmux server starts sessions with its own environment, so
+        // pass on ours. The values show up in the tmux command line until the
+        // session exists.
+        let variables = env::vars().filter(|(name, _)| {
+            name.starts_with(overrides::PREFIX) || FORWARDED.contains(&name.as_str())
+        });
+
+        // Create a new detached session, tmux waits until it exists
+        let mut command = Command::new("tmux");
+        command
+            .arg("new-session")
+            .arg("-d")
+            .arg("-s")
+            .arg(SESSION)
+            .arg("-c")
+            .arg(env::current_dir()?);
+        for (name, value) in variables {
+            command.arg("-e").arg(format!("{name}={value}"));
+        }
+        let status = command.args(args).status()?;
+
+        if !status.success() {
+            return Err(Error::Multiplexer(self.name()));
+        }
+
+        // Attach to the new session
+        Command::new("tmux")
+            .arg("attach-session")
+            .arg("-t")
+            .arg(SESSION)
+            .spawn()?
+            .wait()?;
+
+        exit(0);
+    }
+
+    fn detach(&self) -> Result<()> {
+        Command::new("tmux")
+            .arg("detach-client")
+            .arg("-s")
+            .arg(SESSION)
+            .spawn()?;
+        Ok(())
+    }
+}
@@ -0,0 +1,338 @@
+use crate::{
+    config::{
+        configuration::{Configuration, Environment},
+        validate,
+    },
+    error::{Error, Result},
+    logger,
+    tokenmgr::TokenResult,
+};
+use oauth2::{
+    basic::{
+        BasicErrorResponse, BasicErrorResponseType, BasicRevocationErrorResponse,
+        BasicTokenIntrospectionResponse, BasicTokenType,
+    },
+    reqwest::http_client,
+    url::{form_urlencoded, Url},
+    AuthUrl, AuthorizationCode, ClientId, ClientSecret, ConfigurationError, CsrfToken,
+    PkceCodeChallenge, PkceCodeVerifier, RedirectUrl, RequestTokenError, RevocationUrl, Scope,
+    StandardRevocableToken, TokenUrl,
+};
+use std::{
+    str::FromStr,
+    time::{Duration, Instant},
+};
+
+const TOKEN_URL: &str = "https://api.ebay.com/identity/v1/oauth2/token";
+const AUTH_URL: &str = "https://auth.ebay.com/oauth2/authorize";
+const SANDBOX_TOKEN_URL: &str = "https://api.sandbox.ebay.com/identity/v1/oauth2/token";
+const SANDBOX_AUTH_URL: &str = "https://auth.sandbox.ebay.com/oauth2/authorize";
+const REDIRECT_URL: &str =
+    "https://signin.ebay.com/ws/eBayISAPI.dll?ThirdPartyAuthSucessFailure&isAuthSuccessful=true";
+/// How long eBay accepts an authorization code. The consent has to be
+/// completed within this time after the consent URL was generated.
+pub const CODE_LIFETIME: Duration = Duration::from_secs(299);
+pub const SCOPES: [&str; 12] = [
+    "https://api.ebay.com/oauth/api_scope",
+    "https://api.ebay.com/oauth/api_scope/sell.marketing.readonly",
+    "https://api.ebay.com/oauth/api_scope/sell.inventory.readonly",
+    "https://api.ebay.com/oauth/api_scope/sell.account.readonly",
+    "https://api.ebay.com/oauth/api_scope/sell.fulfillment.readonly",
+    "https://api.ebay.com/oauth/api_scope/sell.analytics.readonly",
+    "https://api.ebay.com/oauth/api_scope/sell.finances",
+    "https://api.ebay.com/oauth/api_scope/sell.payment.dispute",
+    "https://api.ebay.com/oauth/api_scope/commerce.identity.readonly",
+    "https://api.ebay.com/oauth/api_scope/sell.reputation.readonly",
+    "https://api.ebay.com/oauth/api_scope/commerce.notification.subscription.readonly",
+    "https://api.ebay.com/oauth/api_scope/sell.stores.readonly",
+];
+
+/// OAuth client returning eBay's token responses.
+pub type OAuthClient = oauth2::Client<
+    BasicErrorResponse,
+    TokenResult,
+    BasicTokenType,
+    BasicTokenIntrospectionResponse,
+    StandardRevocableToken,
+    BasicRevocationErrorResponse,
+>;
+
+/// Build the OAuth client for the configured application keys.
+///
+/// Fails if the configuration is incomplete or malformed.
+pub fn client(config: &Configuration) -> Result<OAuthClient> {
+    validate::require_credentials(config)?;
+
+    let (auth_url, token_url) = match config.environment {
+        Environment::Production => (AUTH_URL, TOKEN_URL),
+        Environment::Sandbox => (SANDBOX_AUTH_URL, SANDBOX_TOKEN_URL),
+    };
+
+    let mut client = OAuthClient::new(
+        ClientId::new(config.appid.to_string()),
+        Some(ClientSecret::new(config.certid.expose().into())),
+        AuthUrl::new(auth_url.to_string())?,
+        Some(TokenUrl::new(token_url.to_string())?),
+    )
+    .set_redirect_uri(RedirectUrl::new(REDIRECT_URL.to_string())?);
+
+    if let Some(url) = &config.revocation_url {
+        client = client.set_revocation_uri(RevocationUrl::new(url.to_string())?);
+    }
+
+    Ok(client)
+}
+
+/// Revoke `token` at the provider.
+pub fn revoke(client: &OAuthClient, token: StandardRevocableToken) -> Result<()> {
+    client
+        .revoke_token(token)
+        .map_err(|why| match why {
+            ConfigurationError::MissingUrl(..) => Error::NoRevocationUrl,
+            _ => Error::Revocation,
+        })?
+        .request(http_client)
+        .map_err(|_| Error::Revocation)
+}
+
+/// An authorization code grant waiting for the user's consent.
+pub struct Authorization {
+    pub url: Url,
+    csrf_token: CsrfToken,
+    pkce_verifier: PkceCodeVerifier,
+    deadline: Instant,
+}
+
+impl Authorization {
+    #[must_use]
+    pub fn new(client: &OAuthClient, scopes: &[Box<str>]) -> Self {
+        let (pkce_challenge, pkce_verifier) = PkceCodeChallenge::new_random_sha256();
+
+        let mut auth_request = client.authorize_url(CsrfToken::new_random);
+        for scope in scopes {
+            auth_request = auth_request.add_scope(Scope::new(scope.to_string()));
+        }
+        let (url, csrf_token) = auth_request.set_pkce_challenge(pkce_challenge).url();
+
+        Self {
+            url,
+            csrf_token,
+            pkce_verifier,
+            deadline: Instant::now() + CODE_LIFETIME,
+        }
+    }
+
+    /// Time after which the consent can no longer be completed.
+    #[must_use]
+    pub fn deadline(&self) -> Instant {
+        self.deadline
+    }
+
+    #[must_use]
+    pub fn is_expired(&self) -> bool {
+        Instant::now() >= self.deadline
+    }
+
+    /// Extract the authorization code from user input.
+    ///
+    /// Accepts either the full URL the browser was redirected to after
+    /// consent, or the bare (optionally percent-encoded) code. The `state`
+    /// of a URL is validated, a bare code can't be checked.
+    pub fn parse_input(&self, input: &str) -> Result<AuthorizationCode> {
+        let input = input.trim();
+
+        if input.is_empty() {
+            return Err(Error::MissingCode);
+        }
+
+        if let Ok(url) = Url::from_str(input) {
+            return self.code_from_redirect(&url);
+        }
+
+        if input.contains('%') {
+            let query = format!("code={input}");
+            let (_, code) = form_urlencoded::parse(query.as_bytes())
+                .next()
+                .ok_or(Error::MissingCode)?;
+
+            return Ok(AuthorizationCode::new(code.into_owned()));
+        }
+
+        Ok(AuthorizationCode::new(input.to_string()))
+    }
+
+    /// Extract the authorization code from the URL eBay redirected to.
+    ///
+    /// Fails if consent was declined or the `state` parameter does not belong
+    /// to this authorization.
+    pub fn code_from_redirect(&self, url: &Url) -> Result<AuthorizationCode> {
+        let param = |name: &str| {
+            url.query_pairs()
+                .find(|(key, _)| key == name)
+                .map(|(_, value)| value.into_owned())
+        };
+
+        let state = param("state");
+        if state
+            .as_deref()
+            .is_some_and(|state| state != self.csrf_token.secret())
+        {
+            return Err(Error::StateMismatch);
+        }
+
+        if let Some(error) = param("error") {
+            let description = param("error_description").unwrap_or(error);
+            return Err(Error::ConsentDeclined(description.into()));
+        }
+
+        if param("isAuthSuccessful").is_some_and(|value| value == "false") {
+            return Err(Error::ConsentDeclined("access denied".into()));
+        }
+
+        let code = param("code").ok_or(Error::MissingCode)?;
+        logger::register_token(&code);
+
+        if state.is_none() {
+            return Err(Error::StateMismatch);
+        }
+
+        Ok(AuthorizationCode::new(code))
+    }
+
+    /// Exchange the authorization code for a token.
+    pub fn complete(self, client: &OAuthClient, code: AuthorizationCode) -> Result<TokenResult> {
+        if self.is_expired() {
+            return Err(Error::AuthorizationExpired);
+        }
+
+        client
+            .exchange_code(code)
+            .set_pkce_verifier(self.pkce_verifier)
+            .request(http_client)
+            .map_err(|why| match why {
+                RequestTokenError::ServerResponse(response)
+                    if *response.error() == BasicErrorResponseType::InvalidGrant =>
+                {
+                    Error::CodeRejected
+                }
+                _ => Error::TokenRequest,
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Authorization, OAuthClient, AUTH_URL, TOKEN_URL};
+    use crate::error::Error;
+    use oauth2::{AuthUrl, ClientId, TokenUrl};
+
+    fn authorization() -> Authorization {
+        let client = OAuthClient::new(
+            ClientId::new("Example-App-PRD-0123456789".into()),
+            None,
+            AuthUrl::new(AUTH_URL.into()).unwrap(),
+            Some(TokenUrl::new(TOKEN_URL.into()).unwrap()),
+        );
+
+        Authorization::new(&client, &["https://api.ebay.com/oauth/api_scope".into()])
+    }
+
+    fn redirect(authorization: &Authorization, query: &str) -> String {
+        format!(
+            "https://signin.ebay.com/ws/eBayISAPI.dll?ThirdPartyAuthSucessFailure&state={}&{query}",
+            authorization.csrf_token.secret()
+        )
+    }
+
+    #[test]
+    fn code_from_redirect() {
+        let authorization = authorization();
+        let input = redirect(
+            &authorization,
+            "isAuthSuccessful=true&code=v%5E1.1%23i%5E1&expires_in=299",
+        );
+
+        let code = authorization.parse_input(&input).unwrap();
+        assert_eq!(code.secret(), "v^1.1#i^1");
+    }
+
+    #[test]
+    fn state_mismatch() {
+        let authorization = authorization();
+        let input = "https://signin.ebay.com/ws/eBayISAPI.dll?state=other&code=abc";
+
+        assert!(matches!(
+            authorization.parse_input(input),
+            Err(Error::StateMismatch)
+        ));
+    }
+
+    #[test]
+    fn missing_state() {
+        let authorization = authorization();
+        let input = "https://signin.ebay.com/ws/eBayISAPI.dll?code=abc";
+
+        assert!(matches!(
+            authorization.parse_input(input),
+            Err(Error::StateMismatch)
+        ));
+    }
+
+    #[test]
+    fn consent_declined() {
+        let authorization = authorization();
+        let input = redirect(&authorization, "isAuthSuccessful=false");
+
+        assert!(matches!(
+            authorization.parse_input(&input),
+            Err(Error::ConsentDeclined(_))
+        ));
+    }
+
+    #[test]
+    fn consent_error() {
+        let authorization = authorization();
+        let input = redirect(
+            &authorization,
+            "error=access_denied&error_description=User+declined",
+        );
+
+        match authorization.parse_input(&input) {
+            Err(Error::ConsentDeclined(reason)) => assert_eq!(&*reason, "User declined"),
+            other => panic!(
+                "unexpected result: {:?}",
+                other.map(|code| code.secret().clone())
+            ),
+        }
+    }
+
+    #[test]
+    fn missing_code() {
+        let authorization = authorization();
+
+        assert!(matches!(
+            authorization.parse_input(&redirect(&authorization, "isAuthSuccessful=true")),
+            Err(Error::MissingCode)
+        ));
+        assert!(matches!(
+            authorization.parse_input("  "),
+            Err(Error::MissingCode)
+        ));
+    }
+
+    #[test]
+    fn bare_code() {
+        let code = authorization().parse_input(" v^1.1#i^1#f^0 \n").unwrap();
+
+        assert_eq!(code.secret(), "v^1.1#i^1#f^0");
+    }
+
+    #[test]
+    fn percent_encoded_code() {
+        let code = authorization()
+            .parse_input("v%5E1.1%23i%5E1%23f%5E0")
+            .unwrap();
+
+        assert_eq!(code.secret(), "v^1.1#i^1#f^0");
+    }
+}
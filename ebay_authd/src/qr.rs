@@ -0,0 +1,66 @@
+use crate::error::{Error, Result};
+use image::Luma;
+use log::info;
+use oauth2::url::Url;
+use qrcode::{
+    render::{svg, unicode::Dense1x2},
+    EcLevel, QrCode,
+};
+use std::{fs, path::Path};
+
+/// Size in pixels of a single module in image output.
+const MODULE_SIZE: u32 = 4;
+
+/// Print `url` as a QR code using Unicode half-blocks.
+///
+/// Colors are inverted so the code scans on terminals with a dark
+/// background.
+pub fn print(url: &Url) -> Result<()> {
+    let code = encode(url)?;
+    let rendered = code
+        .render::<Dense1x2>()
+        .dark_color(Dense1x2::Light)
+        .light_color(Dense1x2::Dark)
+        .build();
+
+    println!("{rendered}\n");
+    Ok(())
+}
+
+/// Write `url` as a QR code to `path`, as SVG or PNG depending on the
+/// extension.
+pub fn save(url: &Url, path: &Path) -> Result<()> {
+    let code = encode(url)?;
+    let extension = path
+        .extension()
+        .and_then(|extension| extension.to_str())
+        .map(str::to_ascii_lowercase);
+
+    match extension.as_deref() {
+        Some("svg") => {
+            let image = code
+                .render::<svg::Color>()
+                .module_dimensions(MODULE_SIZE, MODULE_SIZE)
+                .build();
+            fs::write(path, image)?;
+        }
+        Some("png") => code
+            .render::<Luma<u8>>()
+            .module_dimensions(MODULE_SIZE, MODULE_SIZE)
+            .build()
+            .save(path)?,
+        _ => return Err(Error::QrFormat(path.into())),
+    }
+
+    info!("Wrote QR code to {}", path.display());
+    Ok(())
+}
+
+fn encode(url: &Url) -> Result<QrCode> {
+    // The consent URL is long, use the lowest error correction to keep the
+    // code small enough for a terminal
+    Ok(QrCode::with_error_correction_level(
+        url.as_str(),
+        EcLevel::L,
+    )?)
+}
@@ -0,0 +1,204 @@
+use crate::{
+    error::{Error, Result},
+    oauth::Authorization,
+};
+use log::{debug, info, warn};
+use nix::{
+    errno::Errno,
+    poll::{poll, PollFd, PollFlags, PollTimeout},
+};
+use oauth2::{url::Url, AuthorizationCode};
+use std::{
+    io::{self, BufRead, BufReader, Write},
+    net::{TcpListener, TcpStream},
+    os::fd::AsFd,
+    process::{Command, Stdio},
+    time::{Duration, Instant},
+};
+
+#[cfg(target_os = "macos")]
+const OPEN_COMMAND: &str = "open";
+#[cfg(not(target_os = "macos"))]
+const OPEN_COMMAND: &str = "xdg-open";
+
+/// How long a connection may take to send its request line. Browsers open
+/// idle connections ahead of time, which must not block the real redirect.
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(5);
+
+const SUCCESS_PAGE: &str = "<h1>Authorization successful</h1>\
+    <p>ebay_authd received the authorization, you can close this window.</p>";
+
+/// Wait for the browser to be redirected to `address` after consent.
+///
+/// This is meant for setups where the RuName's accept URL points to a
+/// host/port we control, e.g. through a local reverse proxy. Requests that
+/// don't carry a valid authorization response are answered with an error
+/// page and otherwise ignored. Fails once the authorization expires.
+pub fn await_redirect(address: &str, authorization: &Authorization) -> Result<AuthorizationCode> {
+    let listener = TcpListener::bind(address)?;
+    info!(
+        "Waiting for the consent redirect on {}",
+        listener.local_addr()?
+    );
+
+    loop {
+        let left = authorization
+            .deadline()
+            .saturating_duration_since(Instant::now());
+        if left.is_zero() {
+            return Err(Error::AuthorizationExpired);
+        }
+
+        let mut fds = [PollFd::new(listener.as_fd(), PollFlags::POLLIN)];
+        let timeout = PollTimeout::try_from(left).unwrap_or(PollTimeout::MAX);
+        match poll(&mut fds, timeout) {
+            Ok(0) | Err(Errno::EINTR) => continue,
+            Ok(..) => (),
+            Err(why) => return Err(why.into()),
+        }
+
+        let (mut stream, _) = listener.accept()?;
+        stream.set_read_timeout(Some(left.min(REQUEST_TIMEOUT)))?;
+
+        let url = match read_request(&mut stream) {
+            Ok(Some(url)) => url,
+            Ok(None) => {
+                respond(&mut stream, "404 Not Found", "<h1>Not found</h1>");
+                continue;
+            }
+            Err(why) => {
+                debug!("Dropping connection without request: {why}");
+                continue;
+            }
+        };
+
+        match authorization.code_from_redirect(&url) {
+            Ok(code) => {
+                respond(&mut stream, "200 OK", SUCCESS_PAGE);
+                return Ok(code);
+            }
+            Err(Error::MissingCode) => {
+                debug!(
+                    "Ignoring request without authorization code: {}",
+                    url.path()
+                );
+                respond(&mut stream, "404 Not Found", "<h1>Not found</h1>");
+            }
+            Err(why @ Error::ConsentDeclined(..)) => {
+                respond(&mut stream, "200 OK", &failure_page(&why));
+                return Err(why);
+            }
+            Err(why) => {
+                warn!("Rejected redirect: {why}");
+                respond(&mut stream, "400 Bad Request", &failure_page(&why));
+            }
+        }
+    }
+}
+
+/// Open `url` in the user's default browser.
+pub fn open_browser(url: &Url) {
+    let result = Command::new(OPEN_COMMAND)
+        .arg(url.as_str())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn();
+
+    if let Err(why) = result {
+        warn!("Failed to open browser using `{OPEN_COMMAND}`: {why}");
+    }
+}
+
+/// Read the request line and return the requested URL, `None` if it is not
+/// a `GET` request.
+///
+/// Fails if the connection is closed or idle until its read timeout.
+fn read_request(stream: &mut TcpStream) -> io::Result<Option<Url>> {
+    let mut request_line = String::new();
+    BufReader::new(&*stream).read_line(&mut request_line)?;
+
+    let mut parts = request_line.split_whitespace();
+    let (Some("GET"), Some(target)) = (parts.next(), parts.next()) else {
+        return Ok(None);
+    };
+
+    Ok(Url::parse("http://localhost")
+        .ok()
+        .and_then(|base| base.join(target).ok()))
+}
+
+fn respond(stream: &mut TcpStream, status: &str, body: &str) {
+    let body = format!(
+        "<!DOCTYPE html><html><head><meta charset=\"utf-8\"><title>ebay_authd</title></head>\
+         <body>{body}</body></html>"
+    );
+    let response = format!(
+        "HTTP/1.1 {status}\r\nContent-Type: text/html; charset=utf-8\r\n\
+         Content-Length: {}\r\nConnection: close\r\n\r\n{body}",
+        body.len()
+    );
+
+    if let Err(why) = stream.write_all(response.as_bytes()) {
+        warn!("Failed to respond to browser: {why}");
+    }
+}
+
+fn failure_page(why: &Error) -> String {
+    let message = why
+        .to_string()
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;");
+
+    format!("<h1>Authorization failed</h1><p>{message}</p>")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::read_request;
+    use std::{
+        io::Write,
+        net::{TcpListener, TcpStream},
+        time::{Duration, Instant},
+    };
+
+    fn connect() -> (TcpStream, TcpStream) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let client = TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+        let (server, _) = listener.accept().unwrap();
+
+        (client, server)
+    }
+
+    #[test]
+    fn idle_connection_times_out() {
+        let (_client, mut server) = connect();
+        server
+            .set_read_timeout(Some(Duration::from_millis(100)))
+            .unwrap();
+
+        let start = Instant::now();
+        assert!(read_request(&mut server).is_err());
+        assert!(start.elapsed() < Duration::from_secs(5));
+    }
+
+    #[test]
+    fn reads_get_request() {
+        let (mut client, mut server) = connect();
+        client
+            .write_all(b"GET /accepted?state=abc&code=xyz HTTP/1.1\r\nHost: localhost\r\n\r\n")
+            .unwrap();
+
+        let url = read_request(&mut server).unwrap().unwrap();
+        assert_eq!(url.path(), "/accepted");
+        assert_eq!(url.query(), Some("state=abc&code=xyz"));
+    }
+
+    #[test]
+    fn ignores_other_methods() {
+        let (mut client, mut server) = connect();
+        client.write_all(b"POST / HTTP/1.1\r\n\r\n").unwrap();
+
+        assert!(read_request(&mut server).unwrap().is_none());
+    }
+}
@@ -0,0 +1,97 @@
+use crate::{
+    config::configuration::Configuration, error::Result, lifecycle::Daemon, logger, watch::Watcher,
+};
+use log::{error, info, warn};
+use std::path::Path;
+
+/// Reloads the configuration of a running daemon.
+///
+/// The file is read by the refresh helper, so the Cert ID of the
+/// configuration kept here is always empty.
+pub struct Reloader {
+    config: Configuration,
+    watcher: Option<Watcher>,
+}
+
+impl Reloader {
+    /// Start watching `location` for changes to `config`.
+    #[must_use]
+    pub fn new(location: Option<&Path>, config: Configuration) -> Self {
+        let watcher = location.and_then(|path| match Watcher::new(path) {
+            Ok(watcher) => Some(watcher),
+            Err(why) => {
+                warn!("Not watching {} for changes: {why}", path.display());
+                None
+            }
+        });
+
+        Self { config, watcher }
+    }
+
+    /// The configuration currently in effect.
+    #[must_use]
+    pub fn config(&self) -> &Configuration {
+        &self.config
+    }
+
+    /// Whether the configuration file changed since the last call.
+    pub fn changed(&mut self) -> bool {
+        self.watcher.as_mut().is_some_and(Watcher::changed)
+    }
+
+    /// Load and validate the configuration and apply it to `daemon`.
+    ///
+    /// The current configuration stays in effect if the new one is invalid.
+    /// Returns the changed fields that only take effect after a restart.
+    pub fn reload(&mut self, daemon: &mut Daemon) -> Result<Vec<&'static str>> {
+        info!("Reloading configuration");
+        let mut new = daemon.load_config()?;
+
+        let old = &self.config;
+        let mut restart = Vec::new();
+
+        // The grant belongs to the application, a different one needs a new
+        // authorization
+        if new.appid != old.appid {
+            restart.push("appid");
+            new.appid.clone_from(&old.appid);
+        }
+        if new.environment != old.environment {
+            restart.push("environment");
+            new.environment = old.environment;
+        }
+        if new.socket != old.socket {
+            restart.push("socket");
+            new.socket.clone_from(&old.socket);
+        }
+        if new.multiplexer != old.multiplexer {
+            restart.push("multiplexer");
+            new.multiplexer = old.multiplexer;
+        }
+        if new.listen != old.listen {
+            restart.push("listen");
+            new.listen.clone_from(&old.listen);
+        }
+
+        if new.scopes != old.scopes {
+            info!("Applying new scopes to future authorizations");
+            daemon.set_scopes(new.scopes());
+        }
+
+        if new.log_level != old.log_level {
+            let level = new.log_level.unwrap_or_else(logger::default_level);
+            if let Err(why) = daemon.set_log_level(level) {
+                error!("Failed to set the log level of the refresh helper: {why}");
+            }
+        }
+
+        for field in &restart {
+            warn!("Changed `{field}` takes effect after a restart");
+        }
+
+        self.config = new;
+        info!("Configuration reloaded");
+
+        Ok(restart)
+    }
+}
@@ -0,0 +1,413 @@
+use crate::error::{Error, Result};
+use clap::ValueEnum;
+use log::{info, warn};
+use std::path::{Path, PathBuf};
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum SandboxMode {
+    /// No sandbox
+    #[default]
+    Off,
+    /// Sandbox as far as the kernel supports it, only log forbidden syscalls
+    Warn,
+    /// Refuse to start without a sandbox, forbidden syscalls fail
+    Enforce,
+}
+
+/// Restricts the running daemon to the files it needs and the syscalls of
+/// the daemon loop, and of the HTTP client if network access is allowed.
+///
+/// Applies to the calling thread and all threads it starts afterwards.
+#[derive(Clone)]
+pub struct Sandbox {
+    mode: SandboxMode,
+    read: Vec<PathBuf>,
+    write: Vec<PathBuf>,
+    remove: Vec<PathBuf>,
+    network: bool,
+}
+
+impl Sandbox {
+    #[must_use]
+    pub fn new(mode: SandboxMode) -> Self {
+        Self {
+            mode,
+            read: Vec::new(),
+            write: Vec::new(),
+            remove: Vec::new(),
+            network: false,
+        }
+    }
+
+    /// Allow reading files below `path`.
+    pub fn allow_read(&mut self, path: &Path) {
+        self.read.push(path.into());
+    }
+
+    /// Allow reading, creating and removing files below `path`.
+    pub fn allow_write(&mut self, path: &Path) {
+        self.write.push(path.into());
+    }
+
+    /// Allow removing files in the directory `path`.
+    pub fn allow_remove(&mut self, path: &Path) {
+        self.remove.push(path.into());
+    }
+
+    /// Allow HTTPS connections and the threads of the HTTP client, e.g. for
+    /// the refresh helper. Otherwise only UNIX sockets can be created.
+    pub fn allow_network(&mut self) {
+        self.network = true;
+    }
+
+    pub fn apply(self) -> Result<()> {
+        if self.mode == SandboxMode::Off {
+            return Ok(());
+        }
+
+        match platform::apply(&self) {
+            Ok(()) => Ok(()),
+            Err(why) if self.mode == SandboxMode::Warn => {
+                warn!("Running without sandbox: {why}");
+                Ok(())
+            }
+            Err(why) => Err(why),
+        }
+    }
+}
+
+/// Landlock rules for the files and a seccomp filter for the syscalls.
+#[cfg(target_os = "linux")]
+mod platform {
+    use super::{info, warn, Error, Path, PathBuf, Result, Sandbox, SandboxMode};
+    use landlock::{
+        path_beneath_rules, Access, AccessFs, AccessNet, NetPort, Ruleset, RulesetAttr,
+        RulesetCreatedAttr, RulesetError, RulesetStatus, ABI,
+    };
+    use seccompiler::{
+        apply_filter, BpfProgram, SeccompAction, SeccompCmpArgLen, SeccompCmpOp, SeccompCondition,
+        SeccompFilter, SeccompRule, TargetArch,
+    };
+    use std::{collections::BTreeMap, env::consts::ARCH};
+
+    /// Newest Landlock ABI the rules are written for.
+    const ABI: ABI = ABI::V5;
+    /// System directories with libraries, name service and TLS configuration.
+    const SYSTEM: [&str; 6] = ["/etc", "/usr", "/lib", "/lib64", "/proc", "/sys/fs/cgroup"];
+    /// Devices the daemon and its libraries open.
+    const DEVICES: [&str; 3] = ["/dev/null", "/dev/urandom", "/dev/random"];
+    /// Port of the eBay API.
+    const HTTPS_PORT: u16 = 443;
+
+    /// Syscalls of the daemon loop, logging and reloading.
+    const SYSCALLS: &[i64] = &[
+        libc::SYS_accept4,
+        libc::SYS_brk,
+        libc::SYS_clock_getres,
+        libc::SYS_clock_gettime,
+        libc::SYS_clock_nanosleep,
+        libc::SYS_close,
+        libc::SYS_connect,
+        libc::SYS_dup,
+        libc::SYS_dup3,
+        libc::SYS_exit,
+        libc::SYS_exit_group,
+        libc::SYS_faccessat,
+        libc::SYS_faccessat2,
+        libc::SYS_fcntl,
+        libc::SYS_fdatasync,
+        libc::SYS_flock,
+        libc::SYS_fstat,
+        libc::SYS_fstatfs,
+        libc::SYS_fsync,
+        libc::SYS_ftruncate,
+        libc::SYS_futex,
+        libc::SYS_getcwd,
+        libc::SYS_getdents64,
+        libc::SYS_getegid,
+        libc::SYS_geteuid,
+        libc::SYS_getgid,
+        libc::SYS_getpeername,
+        libc::SYS_getpid,
+        libc::SYS_getppid,
+        libc::SYS_getrandom,
+        libc::SYS_getsockname,
+        libc::SYS_getsockopt,
+        libc::SYS_gettid,
+        libc::SYS_gettimeofday,
+        libc::SYS_getuid,
+        libc::SYS_inotify_add_watch,
+        libc::SYS_inotify_rm_watch,
+        libc::SYS_ioctl,
+        libc::SYS_lseek,
+        libc::SYS_madvise,
+        libc::SYS_membarrier,
+        libc::SYS_mlock,
+        libc::SYS_mmap,
+        libc::SYS_mprotect,
+        libc::SYS_mremap,
+        libc::SYS_munlock,
+        libc::SYS_munmap,
+        libc::SYS_nanosleep,
+        libc::SYS_newfstatat,
+        libc::SYS_openat,
+        libc::SYS_pipe2,
+        libc::SYS_ppoll,
+        libc::SYS_prctl,
+        libc::SYS_pread64,
+        libc::SYS_prlimit64,
+        libc::SYS_pselect6,
+        libc::SYS_pwrite64,
+        libc::SYS_read,
+        libc::SYS_readlinkat,
+        libc::SYS_readv,
+        libc::SYS_recvfrom,
+        libc::SYS_recvmsg,
+        libc::SYS_renameat,
+        libc::SYS_renameat2,
+        libc::SYS_restart_syscall,
+        libc::SYS_rseq,
+        libc::SYS_rt_sigaction,
+        libc::SYS_rt_sigprocmask,
+        libc::SYS_rt_sigreturn,
+        libc::SYS_sched_getaffinity,
+        libc::SYS_sched_yield,
+        libc::SYS_sendmsg,
+        libc::SYS_sendto,
+        libc::SYS_set_robust_list,
+        libc::SYS_set_tid_address,
+        libc::SYS_setsockopt,
+        libc::SYS_shutdown,
+        libc::SYS_sigaltstack,
+        libc::SYS_socketpair,
+        libc::SYS_statx,
+        libc::SYS_sysinfo,
+        libc::SYS_tgkill,
+        libc::SYS_uname,
+        libc::SYS_unlinkat,
+        libc::SYS_wait4,
+        libc::SYS_write,
+        libc::SYS_writev,
+    ];
+
+    /// Syscalls of the HTTP client, including its runtime threads and name
+    /// resolution.
+    const NETWORK_SYSCALLS: &[i64] = &[
+        libc::SYS_bind,
+        libc::SYS_clone,
+        libc::SYS_clone3,
+        libc::SYS_epoll_create1,
+        libc::SYS_epoll_ctl,
+        libc::SYS_epoll_pwait,
+        libc::SYS_eventfd2,
+        libc::SYS_recvmmsg,
+        libc::SYS_sendmmsg,
+        libc::SYS_socket,
+        #[cfg(target_arch = "x86_64")]
+        libc::SYS_epoll_wait,
+    ];
+
+    /// Syscalls only x86_64 still has, used by some libc functions.
+    #[cfg(target_arch = "x86_64")]
+    const LEGACY_SYSCALLS: &[i64] = &[
+        libc::SYS_access,
+        libc::SYS_arch_prctl,
+        libc::SYS_dup2,
+        libc::SYS_lstat,
+        libc::SYS_open,
+        libc::SYS_pipe,
+        libc::SYS_poll,
+        libc::SYS_readlink,
+        libc::SYS_rename,
+        libc::SYS_select,
+        libc::SYS_stat,
+        libc::SYS_time,
+        libc::SYS_unlink,
+    ];
+    #[cfg(not(target_arch = "x86_64"))]
+    const LEGACY_SYSCALLS: &[i64] = &[];
+
+    pub fn apply(sandbox: &Sandbox) -> Result<()> {
+        restrict_files(sandbox)?;
+        restrict_syscalls(sandbox.mode, sandbox.network)
+    }
+
+    fn restrict_files(sandbox: &Sandbox) -> Result<()> {
+        let https = sandbox
+            .network
+            .then(|| NetPort::new(HTTPS_PORT, AccessNet::ConnectTcp));
+
+        let status = Ruleset::default()
+            .handle_access(AccessFs::from_all(ABI))
+            .and_then(|ruleset| ruleset.handle_access(AccessNet::from_all(ABI)))
+            .and_then(Ruleset::create)
+            .and_then(|ruleset| {
+                ruleset.add_rules(path_beneath_rules(
+                    SYSTEM
+                        .iter()
+                        .map(Path::new)
+                        .chain(sandbox.read.iter().map(PathBuf::as_path)),
+                    AccessFs::from_read(ABI),
+                ))
+            })
+            .and_then(|ruleset| {
+                ruleset.add_rules(path_beneath_rules(
+                    DEVICES
+                        .iter()
+                        .map(Path::new)
+                        .chain(sandbox.write.iter().map(PathBuf::as_path)),
+                    AccessFs::from_all(ABI),
+                ))
+            })
+            .and_then(|ruleset| {
+                ruleset.add_rules(path_beneath_rules(
+                    sandbox.remove.iter().map(PathBuf::as_path),
+                    AccessFs::RemoveFile,
+                ))
+            })
+            .and_then(|ruleset| ruleset.add_rules(https.into_iter().map(Ok::<_, RulesetError>)))
+            .and_then(|ruleset| ruleset.restrict_self())
+            .map_err(|why| Error::Sandbox(format!("Landlock: {why}").into()))?;
+
+        match status.ruleset {
+            RulesetStatus::FullyEnforced => info!("Landlock rules enforced"),
+            RulesetStatus::PartiallyEnforced => {
+                warn!("Landlock rules partially enforced, the kernel lacks some features");
+            }
+            RulesetStatus::NotEnforced => {
+                return Err(Error::Sandbox(
+                    "Landlock is not supported or enabled by the kernel".into(),
+                ));
+            }
+        }
+
+        Ok(())
+    }
+
+    fn restrict_syscalls(mode: SandboxMode, network: bool) -> Result<()> {
+        let error = |why: seccompiler::Error| Error::Sandbox(format!("seccomp: {why}").into());
+
+        let forbidden = match mode {
+            SandboxMode::Enforce => SeccompAction::Errno(libc::EPERM.unsigned_abs()),
+            _ => SeccompAction::Log,
+        };
+        let mut rules: BTreeMap<_, _> = SYSCALLS
+            .iter()
+            .chain(LEGACY_SYSCALLS)
+            .chain(if network { NETWORK_SYSCALLS } else { &[] })
+            .map(|&syscall| (syscall, Vec::new()))
+            .collect();
+        if !network {
+            // For notifying systemd
+            let unix = SeccompCondition::new(
+                0,
+                SeccompCmpArgLen::Dword,
+                SeccompCmpOp::Eq,
+                libc::AF_UNIX.unsigned_abs().into(),
+            )
+            .and_then(|condition| SeccompRule::new(vec![condition]))
+            .map_err(|why| error(why.into()))?;
+            rules.insert(libc::SYS_socket, vec![unix]);
+        }
+        let arch = TargetArch::try_from(ARCH).map_err(|why| error(why.into()))?;
+
+        let filter = SeccompFilter::new(rules, forbidden, SeccompAction::Allow, arch)
+            .map_err(|why| error(why.into()))?;
+        let program = BpfProgram::try_from(filter).map_err(|why| error(why.into()))?;
+        apply_filter(&program).map_err(error)?;
+
+        info!("seccomp filter installed");
+        Ok(())
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+mod platform {
+    use super::{Error, Result, Sandbox};
+
+    pub fn apply(_: &Sandbox) -> Result<()> {
+        Err(Error::Sandbox("only supported on Linux".into()))
+    }
+}
+
+#[cfg(all(test, target_os = "linux"))]
+mod tests {
+    use super::{Sandbox, SandboxMode};
+    use nix::{
+        sys::wait::{waitpid, WaitStatus},
+        unistd::{execv, fork, ForkResult},
+    };
+    use std::{env, ffi::CString, fs, net::UdpSocket, os::unix::net::UnixDatagram, process};
+
+    /// Exit code of the child when the kernel does not support the sandbox.
+    const UNSUPPORTED: i32 = 2;
+
+    /// Whether `test` succeeds in a forked child after applying `sandbox`,
+    /// `None` if the kernel does not support it.
+    fn in_sandbox(sandbox: Sandbox, test: impl FnOnce() -> bool) -> Option<bool> {
+        // SAFETY: the child only runs `test` and exits
+        match unsafe { fork() }.unwrap() {
+            ForkResult::Child => {
+                let code = match sandbox.apply() {
+                    Ok(()) => i32::from(!test()),
+                    Err(_) => UNSUPPORTED,
+                };
+                // SAFETY: skips the exit handlers of the test harness
+                unsafe { libc::_exit(code) }
+            }
+            ForkResult::Parent { child } => match waitpid(child, None).unwrap() {
+                WaitStatus::Exited(_, UNSUPPORTED) => {
+                    eprintln!("Sandbox not supported by the kernel, skipping");
+                    None
+                }
+                WaitStatus::Exited(_, code) => Some(code == 0),
+                status => panic!("sandboxed child ended with {status:?}"),
+            },
+        }
+    }
+
+    #[test]
+    fn forbids_running_programs() {
+        let mut sandbox = Sandbox::new(SandboxMode::Enforce);
+        sandbox.allow_network();
+
+        let forbidden = in_sandbox(sandbox, || {
+            // Exits with 1 if it is run after all
+            let program = CString::new("/bin/false").unwrap();
+            execv(&program, &[&program]).is_err()
+        });
+        assert_ne!(forbidden, Some(false));
+    }
+
+    #[test]
+    fn forbids_writing_outside_allowed_paths() {
+        let dir = env::temp_dir().join(format!("ebay_authd-sandbox-test-{}", process::id()));
+        let allowed = dir.join("allowed");
+        let other = dir.join("other");
+        fs::create_dir_all(&allowed).unwrap();
+        fs::create_dir_all(&other).unwrap();
+
+        let mut sandbox = Sandbox::new(SandboxMode::Enforce);
+        sandbox.allow_write(&allowed);
+
+        let forbidden = in_sandbox(sandbox, || {
+            fs::write(allowed.join("file"), "allowed").is_ok()
+                && fs::write(other.join("file"), "forbidden").is_err()
+        });
+        fs::remove_dir_all(&dir).unwrap();
+        assert_ne!(forbidden, Some(false));
+    }
+
+    #[test]
+    fn forbids_network_sockets_without_network() {
+        let forbidden = in_sandbox(Sandbox::new(SandboxMode::Enforce), || {
+            UnixDatagram::unbound().is_ok() && UdpSocket::bind("127.0.0.1:0").is_err()
+        });
+        assert_ne!(forbidden, Some(false));
+
+        let mut sandbox = Sandbox::new(SandboxMode::Enforce);
+        sandbox.allow_network();
+        let allowed = in_sandbox(sandbox, || UdpSocket::bind("127.0.0.1:0").is_ok());
+        assert_ne!(allowed, Some(false));
+    }
+}
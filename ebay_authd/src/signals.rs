@@ -0,0 +1,62 @@
+use crate::error::Result;
+use nix::sys::signal::{sigaction, SaFlags, SigAction, SigHandler, SigSet, Signal};
+use std::sync::atomic::{AtomicBool, Ordering};
+
+static STOP: AtomicBool = AtomicBool::new(false);
+static RELOAD: AtomicBool = AtomicBool::new(false);
+
+/// Request a clean shutdown on `SIGINT` (Ctrl-C) and `SIGTERM`, and a
+/// configuration reload on `SIGHUP`.
+pub fn install() -> Result<()> {
+    let action = SigAction::new(
+        SigHandler::Handler(handle_stop),
+        SaFlags::empty(),
+        SigSet::empty(),
+    );
+
+    for signal in [Signal::SIGINT, Signal::SIGTERM] {
+        // SAFETY: the handler only stores to an atomic
+        unsafe { sigaction(signal, &action) }?;
+    }
+
+    let action = SigAction::new(
+        SigHandler::Handler(handle_reload),
+        SaFlags::empty(),
+        SigSet::empty(),
+    );
+    // SAFETY: see above
+    unsafe { sigaction(Signal::SIGHUP, &action) }?;
+
+    Ok(())
+}
+
+/// Ignore the signals handled by [`install`], e.g. in the refresh helper,
+/// which stops along with the socket server.
+pub fn ignore() -> Result<()> {
+    let action = SigAction::new(SigHandler::SigIgn, SaFlags::empty(), SigSet::empty());
+
+    for signal in [Signal::SIGINT, Signal::SIGTERM, Signal::SIGHUP] {
+        // SAFETY: ignoring a signal installs no handler
+        unsafe { sigaction(signal, &action) }?;
+    }
+
+    Ok(())
+}
+
+/// Whether a stop signal has been received.
+pub fn stop_requested() -> bool {
+    STOP.load(Ordering::Relaxed)
+}
+
+/// Whether a reload signal has been received since the last call.
+pub fn reload_requested() -> bool {
+    RELOAD.swap(false, Ordering::SeqCst)
+}
+
+extern "C" fn handle_stop(_: nix::libc::c_int) {
+    STOP.store(true, Ordering::SeqCst);
+}
+
+extern "C" fn handle_reload(_: nix::libc::c_int) {
+    RELOAD.store(true, Ordering::SeqCst);
+}
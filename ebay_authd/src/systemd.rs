@@ -0,0 +1,114 @@
+use crate::{error::Result, lifecycle::Daemon};
+use ebay_authd_core::state::State;
+use log::{debug, warn};
+use sd_notify::NotifyState;
+use std::{
+    os::{fd::FromRawFd, unix::net::UnixListener},
+    time::{Duration, Instant},
+};
+
+/// How often the status text is updated when the watchdog is disabled.
+const STATUS_INTERVAL: Duration = Duration::from_secs(30);
+
+/// Take over the listening socket passed by systemd (socket activation).
+pub fn activated_listener() -> Result<Option<UnixListener>> {
+    let Some(fd) = sd_notify::listen_fds()?.next() else {
+        return Ok(None);
+    };
+
+    debug!("Using socket passed by systemd (fd {fd})");
+
+    // SAFETY: systemd passes ownership of the descriptor to this process
+    Ok(Some(unsafe { UnixListener::from_raw_fd(fd) }))
+}
+
+/// Readiness, status and watchdog notifications for the service manager.
+///
+/// Readiness is signalled the first time the daemon holds a token, so units
+/// ordered after the service can rely on it. Until then, the status text
+/// reports the daemon's state, e.g. `Unauthenticated` after a headless start.
+///
+/// All notifications are no-ops when not running under systemd.
+pub struct Notifier {
+    interval: Duration,
+    watchdog: bool,
+    last: Instant,
+    /// Whether `READY=1` was sent
+    ready: bool,
+}
+
+impl Notifier {
+    #[must_use]
+    pub fn new() -> Self {
+        let mut usec = 0;
+        let watchdog = sd_notify::watchdog_enabled(false, &mut usec);
+
+        // Ping at twice the rate systemd expects
+        let interval = if watchdog {
+            debug!("systemd watchdog enabled ({usec}us)");
+            Duration::from_micros(usec / 2)
+        } else {
+            STATUS_INTERVAL
+        };
+
+        Self {
+            interval,
+            watchdog,
+            last: Instant::now(),
+            ready: false,
+        }
+    }
+
+    /// Report the state once the socket is up.
+    pub fn started(&mut self, daemon: &Daemon) {
+        self.update(daemon, true);
+    }
+
+    #[allow(clippy::unused_self)]
+    pub fn stopping(&self) {
+        notify(&[NotifyState::Stopping]);
+    }
+
+    /// Signal readiness once the daemon holds a token, and send watchdog
+    /// pings and status updates when they are due.
+    pub fn tick(&mut self, daemon: &Daemon) {
+        self.update(daemon, false);
+    }
+
+    fn update(&mut self, daemon: &Daemon, force: bool) {
+        let becomes_ready = !self.ready && daemon.state() == State::Ready;
+        if !force && !becomes_ready && self.last.elapsed() < self.interval {
+            return;
+        }
+
+        let status = status(daemon);
+        let mut states = vec![NotifyState::Status(&status)];
+
+        if becomes_ready {
+            self.ready = true;
+            states.push(NotifyState::Ready);
+        }
+        if self.watchdog {
+            states.push(NotifyState::Watchdog);
+        }
+
+        notify(&states);
+
+        self.last = Instant::now();
+    }
+}
+
+fn notify(state: &[NotifyState]) {
+    if let Err(why) = sd_notify::notify(false, state) {
+        warn!("Failed to notify systemd: {why}");
+    }
+}
+
+fn status(daemon: &Daemon) -> String {
+    let state = daemon.state();
+
+    match daemon.access() {
+        Some(access) => format!("{state}, token expires in {}s", access.expiry().as_secs()),
+        None => state.to_string(),
+    }
+}
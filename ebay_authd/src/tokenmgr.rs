@@ -1,93 +1,245 @@
-use crate::error::{Error, Result};
+use crate::{
+    error::{Error, Result},
+    logger, memory,
+    oauth::{self, OAuthClient},
+};
+use ebay_authd_core::secret::Secret;
 use log::info;
 use oauth2::{
-    basic::{BasicClient, BasicTokenType},
+    basic::{BasicErrorResponseType, BasicTokenType},
     reqwest::http_client,
-    EmptyExtraTokenFields, RefreshToken, StandardTokenResponse, TokenResponse,
+    AccessToken, ExtraTokenFields, RefreshToken, RequestTokenError, StandardRevocableToken,
+    StandardTokenResponse, TokenResponse,
+};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::{
+    fmt,
+    time::{Duration, Instant},
 };
-use std::time::{Duration, Instant};
 
-type TokenResult = StandardTokenResponse<EmptyExtraTokenFields, BasicTokenType>;
+pub type TokenResult = StandardTokenResponse<EbayTokenFields, BasicTokenType>;
+
+/// Non-standard fields of eBay's token response.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct EbayTokenFields {
+    /// Lifetime of the refresh token in seconds
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub refresh_token_expires_in: Option<u64>,
+}
 
-#[derive(Debug)]
+impl ExtraTokenFields for EbayTokenFields {}
+
+/// Keeps the access token fresh.
+///
+/// The tokens are zeroed when dropped, and the refresh token is kept in
+/// locked memory. Copies made while talking to eBay are outside our control.
 pub struct TokenManager {
-    client: BasicClient,
-    token: TokenResult,
-    refresh_token: RefreshToken,
+    client: OAuthClient,
+    token: Secret,
+    expires_in: Duration,
+    refresh_token: Secret,
     refresh: Instant,
 }
 
 impl TokenManager {
-    #[must_use]
-    pub fn new(client: BasicClient, token: TokenResult) -> Self {
-        let refresh_token = token.refresh_token().cloned().unwrap();
+    /// Manage the token obtained from an authorization code grant.
+    pub fn new(client: OAuthClient, token: TokenResult) -> Result<Self> {
+        let refresh_token = token.refresh_token().ok_or(Error::MissingRefreshToken)?;
+        register(&token);
+
+        Ok(Self::with_refresh_token(
+            client,
+            &token,
+            refresh_token.secret().as_str().into(),
+        ))
+    }
+
+    /// Manage an existing refresh token, e.g. one obtained by other tooling.
+    ///
+    /// The refresh token is validated by immediately requesting an access
+    /// token with it.
+    pub fn from_refresh_token(client: OAuthClient, refresh_token: RefreshToken) -> Result<Self> {
+        info!("Validating refresh token");
+        let refresh_token = Secret::new(refresh_token.secret().as_str());
+        logger::register_secret(refresh_token.expose());
+        let token = request(&client, &refresh_token).map_err(|why| match why {
+            Error::GrantRejected => Error::RefreshTokenRejected,
+            why => why,
+        })?;
+        register(&token);
+
+        let refresh_token = match token.refresh_token() {
+            Some(rotated) => rotated.secret().as_str().into(),
+            None => refresh_token,
+        };
+
+        Ok(Self::with_refresh_token(client, &token, refresh_token))
+    }
+
+    fn with_refresh_token(client: OAuthClient, token: &TokenResult, refresh_token: Secret) -> Self {
+        memory::lock(refresh_token.expose());
 
         Self {
             client,
-            token,
+            token: token.access_token().secret().as_str().into(),
+            expires_in: token.expires_in().unwrap_or_default(),
             refresh_token,
             refresh: Instant::now(),
         }
     }
 
-    #[must_use]
-    pub fn get_token(&self) -> String {
-        self.token.access_token().secret().to_string()
-    }
-
-    #[must_use]
-    pub fn get_token_bytes(&self) -> Box<[u8]> {
-        self.token.access_token().secret().bytes().collect()
+    pub fn set_client(&mut self, client: OAuthClient) {
+        self.client = client;
     }
 
     #[must_use]
-    pub fn expires_soon(&self) -> bool {
-        self.expiry() <= Duration::from_secs(10)
+    pub fn get_token(&self) -> Secret {
+        self.token.clone()
     }
 
     #[must_use]
     pub fn expiry(&self) -> Duration {
-        self.token.expires_in().unwrap() - self.refresh.elapsed()
+        self.expires_in.saturating_sub(self.refresh.elapsed())
     }
 
     pub fn refresh(&mut self) -> Result<()> {
         info!("Refreshing token");
-        let new_token = self
-            .client
-            .exchange_refresh_token(&self.refresh_token)
-            .request(http_client)
-            .map_err(|_| Error::TokenRequest)?;
+        let new_token = request(&self.client, &self.refresh_token)?;
+        register(&new_token);
 
         self.refresh = Instant::now();
-        self.token = new_token;
+        self.token = new_token.access_token().secret().as_str().into();
+        self.expires_in = new_token.expires_in().unwrap_or_default();
 
-        Ok(())
-    }
-
-    pub fn tick(&mut self) -> Result<()> {
-        if !self.expires_soon() {
-            return Ok(());
+        if let Some(refresh_token) = new_token.refresh_token() {
+            memory::unlock(self.refresh_token.expose());
+            self.refresh_token = refresh_token.secret().as_str().into();
+            memory::lock(self.refresh_token.expose());
         }
 
-        self.refresh()?;
-
         Ok(())
     }
 
+    /// Revoke the refresh and access token at the provider.
+    ///
+    /// The tokens are dropped along with the manager, whether revocation
+    /// succeeds or not.
+    pub fn revoke(self) -> Result<()> {
+        info!("Revoking grant");
+        oauth::revoke(
+            &self.client,
+            StandardRevocableToken::RefreshToken(RefreshToken::new(
+                self.refresh_token.expose().into(),
+            )),
+        )?;
+        oauth::revoke(
+            &self.client,
+            StandardRevocableToken::AccessToken(AccessToken::new(self.token.expose().into())),
+        )
+    }
+
     pub fn last_refresh(&self) -> Duration {
         self.refresh.elapsed()
     }
 
-    pub fn short_token(&self) -> Box<str> {
-        self.token
-            .access_token()
-            .secret()
-            .get(64..96)
-            .unwrap()
-            .into()
+    /// Fingerprint of the access token, safe to show.
+    #[must_use]
+    pub fn token_fingerprint(&self) -> Box<str> {
+        fingerprint(self.token.expose())
+    }
+
+    /// Fingerprint of the refresh token, safe to show.
+    #[must_use]
+    pub fn refresh_token_fingerprint(&self) -> Box<str> {
+        fingerprint(self.refresh_token.expose())
+    }
+}
+
+impl Drop for TokenManager {
+    fn drop(&mut self) {
+        memory::unlock(self.refresh_token.expose());
+    }
+}
+
+impl fmt::Debug for TokenManager {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("TokenManager")
+            .field("token", &self.token_fingerprint())
+            .field("expiry", &self.expiry())
+            .field("refresh_token", &self.refresh_token_fingerprint())
+            .field("last_refresh", &self.last_refresh())
+            .finish_non_exhaustive()
+    }
+}
+
+/// Identify `token` by the start of its SHA-256 hash and its length, without
+/// revealing any of it.
+fn fingerprint(token: &str) -> Box<str> {
+    let hash = Sha256::digest(token.as_bytes());
+    let hex: String = hash[..8].iter().map(|byte| format!("{byte:02x}")).collect();
+
+    format!("sha256:{hex}/{}", token.len()).into()
+}
+
+/// Mask the tokens in `token` in the log.
+fn register(token: &TokenResult) {
+    logger::register_token(token.access_token().secret());
+
+    if let Some(refresh_token) = token.refresh_token() {
+        logger::register_secret(refresh_token.secret());
+    }
+}
+
+/// Request a new access token using `refresh_token`.
+fn request(client: &OAuthClient, refresh_token: &Secret) -> Result<TokenResult> {
+    client
+        .exchange_refresh_token(&RefreshToken::new(refresh_token.expose().into()))
+        .request(http_client)
+        .map_err(|why| match why {
+            RequestTokenError::ServerResponse(response)
+                if *response.error() == BasicErrorResponseType::InvalidGrant =>
+            {
+                Error::GrantRejected
+            }
+            _ => Error::TokenRequest,
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::fingerprint;
+
+    #[test]
+    fn fingerprint_of_empty_token() {
+        assert_eq!(&*fingerprint(""), "sha256:e3b0c44298fc1c14/0");
+    }
+
+    #[test]
+    fn fingerprint_of_short_token() {
+        // Sandbox and mock tokens can be shorter than any prefix
+        for token in ["a", "abc", "v^1.1#"] {
+            let fingerprint = fingerprint(token);
+
+            assert!(fingerprint.starts_with("sha256:"));
+            assert!(fingerprint.ends_with(&format!("/{}", token.len())));
+        }
+    }
+
+    #[test]
+    fn fingerprint_of_long_token() {
+        let token = format!("v^1.1#i^1#r^1#{}", "Ab3=".repeat(500));
+        let fingerprint = fingerprint(&token);
+
+        assert_eq!(fingerprint.len(), "sha256:".len() + 16 + "/2014".len());
+        assert!(!fingerprint.contains("v^1.1#"));
+        assert!(fingerprint.ends_with("/2014"));
+        assert!(!fingerprint.contains("Ab3="));
+        assert_ne!(fingerprint, super::fingerprint(&token[1..]));
     }
 
-    pub fn short_refresh_token(&self) -> Box<str> {
-        self.refresh_token.secret().get(64..96).unwrap().into()
+    #[test]
+    fn fingerprint_of_multibyte_token() {
+        assert!(fingerprint("äöü").ends_with("/6"));
     }
 }
@@ -0,0 +1,38 @@
+use crate::error::{Error, Result};
+use log::info;
+use nix::unistd::{setgid, setuid, Gid, Uid, User};
+
+/// Look up the account named `name`.
+pub fn lookup(name: &str) -> Result<User> {
+    User::from_name(name)?.ok_or_else(|| Error::User(format!("no user named `{name}`").into()))
+}
+
+/// Permanently switch the process to `user` and its primary group, dropping
+/// all supplementary groups.
+pub fn switch(user: &User) -> Result<()> {
+    if !Uid::effective().is_root() {
+        return Err(Error::User("running as root is required".into()));
+    }
+
+    set_groups(user.gid)?;
+    setgid(user.gid)?;
+    setuid(user.uid)?;
+
+    info!("Serving clients as {} (uid {})", user.name, user.uid);
+    Ok(())
+}
+
+#[cfg(not(target_os = "macos"))]
+fn set_groups(gid: Gid) -> Result<()> {
+    Ok(nix::unistd::setgroups(&[gid])?)
+}
+
+/// nix lacks `setgroups` on macOS.
+#[cfg(target_os = "macos")]
+fn set_groups(gid: Gid) -> Result<()> {
+    // SAFETY: the pointer refers to exactly one group
+    let result = unsafe { nix::libc::setgroups(1, &gid.as_raw()) };
+    nix::errno::Errno::result(result)?;
+
+    Ok(())
+}
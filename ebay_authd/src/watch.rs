@@ -0,0 +1,91 @@
+use crate::error::Result;
+use std::path::Path;
+
+pub use platform::Watcher;
+
+/// Watches the directory of a file with inotify, so changes are noticed
+/// even when editors replace the file instead of writing to it.
+#[cfg(target_os = "linux")]
+mod platform {
+    use super::{Path, Result};
+    use nix::sys::inotify::{AddWatchFlags, InitFlags, Inotify};
+    use std::ffi::OsString;
+
+    pub struct Watcher {
+        inotify: Inotify,
+        name: OsString,
+    }
+
+    impl Watcher {
+        pub fn new(path: &Path) -> Result<Self> {
+            let inotify = Inotify::init(InitFlags::IN_NONBLOCK | InitFlags::IN_CLOEXEC)?;
+            let directory = path
+                .parent()
+                .filter(|parent| !parent.as_os_str().is_empty())
+                .unwrap_or(Path::new("."));
+
+            inotify.add_watch(
+                directory,
+                AddWatchFlags::IN_CLOSE_WRITE
+                    | AddWatchFlags::IN_MOVED_TO
+                    | AddWatchFlags::IN_CREATE,
+            )?;
+
+            Ok(Self {
+                inotify,
+                name: path.file_name().unwrap_or_default().to_owned(),
+            })
+        }
+
+        /// Whether the file changed since the last call.
+        pub fn changed(&mut self) -> bool {
+            let mut changed = false;
+
+            loop {
+                match self.inotify.read_events() {
+                    Ok(events) => {
+                        changed |= events
+                            .iter()
+                            .any(|event| event.name.as_ref() == Some(&self.name));
+                    }
+                    // EAGAIN once all events have been read
+                    Err(..) => return changed,
+                }
+            }
+        }
+    }
+}
+
+/// Compares the modification time of the file, for systems without inotify.
+#[cfg(not(target_os = "linux"))]
+mod platform {
+    use super::{Path, Result};
+    use std::{fs, path::PathBuf, time::SystemTime};
+
+    pub struct Watcher {
+        path: PathBuf,
+        modified: Option<SystemTime>,
+    }
+
+    impl Watcher {
+        pub fn new(path: &Path) -> Result<Self> {
+            Ok(Self {
+                path: path.into(),
+                modified: modified(path),
+            })
+        }
+
+        /// Whether the file changed since the last call.
+        pub fn changed(&mut self) -> bool {
+            let modified = modified(&self.path);
+            let changed = modified != self.modified;
+            self.modified = modified;
+
+            changed
+        }
+    }
+
+    fn modified(path: &Path) -> Option<SystemTime> {
+        fs::metadata(path).and_then(|meta| meta.modified()).ok()
+    }
+}
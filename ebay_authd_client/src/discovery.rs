@@ -0,0 +1,309 @@
+use serde::Deserialize;
+use std::{
+    env, fmt, fs, io,
+    os::unix::{
+        fs::MetadataExt,
+        net::{UnixListener, UnixStream},
+    },
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+
+/// Environment variable used to override the socket location.
+pub const SOCKET_ENV: &str = "EBAY_AUTHD_SOCKET";
+/// Environment variable used to override the configuration file location.
+pub const CONFIG_ENV: &str = "EBAY_AUTHD_CONFIG";
+
+const SOCKET_DIR: &str = "ebay_authd";
+const SOCKET_NAME: &str = "ebay_authd.sock";
+const CONFIG_NAME: &str = "ebay_authd";
+/// Supported configuration file extensions, in order of preference.
+const CONFIG_EXTENSIONS: [&str; 4] = ["yml", "yaml", "toml", "json"];
+
+/// Location of the daemon's UNIX socket.
+///
+/// Abstract namespace sockets (Linux only) are written with a leading `@`,
+/// for example `@ebay_authd`.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum SocketAddress {
+    Path(PathBuf),
+    Abstract(Box<str>),
+}
+
+impl SocketAddress {
+    pub fn connect(&self) -> io::Result<UnixStream> {
+        match self {
+            Self::Path(path) => {
+                check_socket_dir(path)?;
+                UnixStream::connect(path)
+            }
+            Self::Abstract(name) => abstract_socket::connect(name),
+        }
+    }
+
+    pub fn bind(&self) -> io::Result<UnixListener> {
+        match self {
+            Self::Path(path) => UnixListener::bind(path),
+            Self::Abstract(name) => abstract_socket::bind(name),
+        }
+    }
+
+    /// Filesystem path of the socket, `None` for abstract sockets.
+    #[must_use]
+    pub fn path(&self) -> Option<&Path> {
+        match self {
+            Self::Path(path) => Some(path),
+            Self::Abstract(..) => None,
+        }
+    }
+}
+
+impl FromStr for SocketAddress {
+    type Err = io::Error;
+
+    fn from_str(value: &str) -> io::Result<Self> {
+        if value.is_empty() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "empty socket address",
+            ));
+        }
+
+        match value.strip_prefix('@') {
+            Some(name) if abstract_socket::SUPPORTED => Ok(Self::Abstract(name.into())),
+            Some(..) => Err(io::Error::new(
+                io::ErrorKind::Unsupported,
+                "abstract sockets are only supported on Linux",
+            )),
+            None => Ok(Self::Path(value.into())),
+        }
+    }
+}
+
+impl fmt::Display for SocketAddress {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Path(path) => write!(f, "{}", path.display()),
+            Self::Abstract(name) => write!(f, "@{name}"),
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct SocketConfig {
+    socket: Option<Box<str>>,
+}
+
+/// Find the daemon socket the same way the `ebay_authd` binary does.
+///
+/// Checks `EBAY_AUTHD_SOCKET` first, then the `socket` field of the
+/// configuration file, and finally falls back to [`default_socket`].
+#[must_use]
+pub fn discover() -> SocketAddress {
+    if let Some(address) = env::var(SOCKET_ENV)
+        .ok()
+        .and_then(|value| value.parse().ok())
+    {
+        return address;
+    }
+
+    configured_socket().unwrap_or_else(default_socket)
+}
+
+/// Path of the daemon configuration file.
+///
+/// This is `EBAY_AUTHD_CONFIG` if set, otherwise `ebay_authd.yml` (or
+/// `.yaml`, `.toml`, `.json`, whichever exists) in `$XDG_CONFIG_HOME`, which
+/// defaults to `~/.config`. Returns `None` if there is no home directory.
+#[must_use]
+pub fn config_location() -> Option<PathBuf> {
+    if let Some(path) = env::var_os(CONFIG_ENV).filter(|path| !path.is_empty()) {
+        return Some(path.into());
+    }
+
+    let dir = match env::var_os("XDG_CONFIG_HOME") {
+        Some(dir) if Path::new(&dir).is_absolute() => PathBuf::from(dir),
+        _ => homedir::my_home().ok().flatten()?.join(".config"),
+    };
+
+    let candidates =
+        CONFIG_EXTENSIONS.map(|extension| dir.join(format!("{CONFIG_NAME}.{extension}")));
+    let existing = candidates.iter().find(|path| path.is_file()).cloned();
+
+    existing.or_else(|| candidates.into_iter().next())
+}
+
+/// Default socket location.
+///
+/// This is `$XDG_RUNTIME_DIR/ebay_authd/ebay_authd.sock`, or a per-user
+/// directory inside the system temporary directory if `XDG_RUNTIME_DIR`
+/// is not set.
+#[must_use]
+pub fn default_socket() -> SocketAddress {
+    let mut path = match env::var_os("XDG_RUNTIME_DIR") {
+        Some(dir) if !dir.is_empty() => {
+            let mut path = PathBuf::from(dir);
+            path.push(SOCKET_DIR);
+            path
+        }
+        _ => temp_socket_dir(),
+    };
+
+    path.push(SOCKET_NAME);
+    SocketAddress::Path(path)
+}
+
+/// Refuse to use `path` if it is in the per-user directory inside the
+/// system temporary directory, and that directory is not private.
+///
+/// Other users can create the directory before us, and would then control
+/// the socket, pid and log file. Other locations are either private already
+/// (`XDG_RUNTIME_DIR`) or chosen by the administrator.
+pub fn check_socket_dir(path: &Path) -> io::Result<()> {
+    match path.parent() {
+        Some(dir) if dir == temp_socket_dir() => check_private_dir(dir),
+        _ => Ok(()),
+    }
+}
+
+/// Fallback socket directory if `XDG_RUNTIME_DIR` is not set.
+fn temp_socket_dir() -> PathBuf {
+    env::temp_dir().join(format!("{SOCKET_DIR}-{}", nix::unistd::getuid()))
+}
+
+/// Check that `dir` is a directory (not a symlink) owned by the current user
+/// and only accessible by them.
+fn check_private_dir(dir: &Path) -> io::Result<()> {
+    let metadata = fs::symlink_metadata(dir)?;
+    let refuse = |reason: &str| {
+        Err(io::Error::new(
+            io::ErrorKind::PermissionDenied,
+            format!("refusing to use {}: {reason}", dir.display()),
+        ))
+    };
+
+    if !metadata.is_dir() {
+        return refuse("not a directory");
+    }
+    if metadata.uid() != nix::unistd::geteuid().as_raw() {
+        return refuse("owned by another user");
+    }
+    if metadata.mode() & 0o777 != 0o700 {
+        return refuse("accessible by other users, its mode must be 0700");
+    }
+
+    Ok(())
+}
+
+fn configured_socket() -> Option<SocketAddress> {
+    let path = config_location()?;
+    let contents = fs::read_to_string(&path).ok()?;
+
+    // YAML parsing covers JSON as well
+    let config: SocketConfig = if path
+        .extension()
+        .is_some_and(|extension| extension == "toml")
+    {
+        toml::from_str(&contents).ok()?
+    } else {
+        serde_yaml::from_str(&contents).ok()?
+    };
+
+    config.socket?.parse().ok()
+}
+
+#[cfg(any(target_os = "linux", target_os = "android"))]
+mod abstract_socket {
+    #[cfg(target_os = "android")]
+    use std::os::android::net::SocketAddrExt;
+    #[cfg(target_os = "linux")]
+    use std::os::linux::net::SocketAddrExt;
+    use std::{
+        io,
+        os::unix::net::{SocketAddr, UnixListener, UnixStream},
+    };
+
+    pub const SUPPORTED: bool = true;
+
+    pub fn connect(name: &str) -> io::Result<UnixStream> {
+        UnixStream::connect_addr(&SocketAddr::from_abstract_name(name)?)
+    }
+
+    pub fn bind(name: &str) -> io::Result<UnixListener> {
+        UnixListener::bind_addr(&SocketAddr::from_abstract_name(name)?)
+    }
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "android")))]
+mod abstract_socket {
+    use std::{
+        io,
+        os::unix::net::{UnixListener, UnixStream},
+    };
+
+    pub const SUPPORTED: bool = false;
+
+    fn unsupported() -> io::Error {
+        io::Error::new(
+            io::ErrorKind::Unsupported,
+            "abstract sockets are only supported on Linux",
+        )
+    }
+
+    pub fn connect(_name: &str) -> io::Result<UnixStream> {
+        Err(unsupported())
+    }
+
+    pub fn bind(_name: &str) -> io::Result<UnixListener> {
+        Err(unsupported())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::check_private_dir;
+    use std::{
+        env, fs,
+        os::unix::fs::{symlink, PermissionsExt},
+        path::PathBuf,
+        process,
+    };
+
+    fn dir(name: &str) -> PathBuf {
+        let dir = env::temp_dir().join(format!("ebay_authd-test-{}-{name}", process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn accepts_private_dir() {
+        let dir = dir("private");
+        fs::set_permissions(&dir, fs::Permissions::from_mode(0o700)).unwrap();
+
+        assert!(check_private_dir(&dir).is_ok());
+        fs::remove_dir(&dir).unwrap();
+    }
+
+    #[test]
+    fn refuses_shared_dir() {
+        let dir = dir("shared");
+        fs::set_permissions(&dir, fs::Permissions::from_mode(0o755)).unwrap();
+
+        assert!(check_private_dir(&dir).is_err());
+        fs::remove_dir(&dir).unwrap();
+    }
+
+    #[test]
+    fn refuses_symlink() {
+        let dir = dir("target");
+        fs::set_permissions(&dir, fs::Permissions::from_mode(0o700)).unwrap();
+        let link = dir.with_extension("link");
+        let _ = fs::remove_file(&link);
+        symlink(&dir, &link).unwrap();
+
+        assert!(check_private_dir(&link).is_err());
+        fs::remove_file(&link).unwrap();
+        fs::remove_dir(&dir).unwrap();
+    }
+}
@@ -9,6 +9,9 @@ pub enum Error {
     #[error("Serialization: {0}")]
     Serialize(#[from] ebay_authd_core::SerializeError),
 
+    #[error("Message is not valid UTF-8")]
+    InvalidUtf8,
+
     #[error("Expected response, got request")]
     ExpectedResponse,
 
@@ -1,31 +1,52 @@
-use crate::error::{Error, Result};
+use crate::{
+    discovery::SocketAddress,
+    error::{Error, Result},
+};
 use ebay_authd_core::{request::Request, response::Response, Message};
 use log::debug;
 use std::{
-    io::{BufRead, BufReader, BufWriter, Write},
+    io::{Read, Write},
     os::{
         fd::{AsRawFd, BorrowedFd},
         unix::net::UnixStream,
     },
 };
+use zeroize::{Zeroize, Zeroizing};
 
+pub mod discovery;
 pub mod error;
 
+/// Initial size of the message buffers, large enough for a message with a
+/// token so that they are not reallocated (leaving copies behind).
+const BUFFER_SIZE: usize = 8192;
+
+/// A connection to the daemon, or of the daemon to a client.
+///
+/// Messages are buffered in memory that is overwritten with zeros once they
+/// have been handled, as they may contain tokens.
 pub struct Client {
-    reader: BufReader<UnixStream>,
-    writer: BufWriter<UnixStream>,
+    stream: UnixStream,
+    buffer: Zeroizing<Vec<u8>>,
 }
 
 impl Client {
     pub fn new(stream: UnixStream) -> Result<Self> {
-        let copy = stream.try_clone()?;
-
         Ok(Self {
-            reader: BufReader::new(stream),
-            writer: BufWriter::new(copy),
+            stream,
+            buffer: Zeroizing::new(Vec::with_capacity(BUFFER_SIZE)),
         })
     }
 
+    /// Connect to the daemon listening on `address`.
+    pub fn connect(address: &SocketAddress) -> Result<Self> {
+        Self::new(address.connect()?)
+    }
+
+    /// Connect to the daemon using [`discovery::discover`].
+    pub fn discover() -> Result<Self> {
+        Self::connect(&discovery::discover())
+    }
+
     pub fn exchange(&mut self, request: Request) -> Result<Response> {
         self.message(request)?;
         let message = self.await_message()?.ok_or(Error::BrokenConnection)?;
@@ -36,33 +57,68 @@ impl Client {
 
     pub fn await_message(&mut self) -> Result<Option<Message>> {
         debug!("Waiting for message from client");
-        let mut buffer = String::with_capacity(64);
-
-        if self.reader.read_line(&mut buffer)? == 0 {
-            return Ok(None);
-        }
 
-        buffer.pop();
-        debug!("Received: {buffer}");
-        let message = Message::deserialize(&buffer)?;
+        let end = loop {
+            if let Some(end) = self.buffer.iter().position(|&byte| byte == b'\n') {
+                break end;
+            }
+
+            let mut chunk = Zeroizing::new([0; 1024]);
+            let read = self.stream.read(&mut *chunk)?;
+            if read == 0 {
+                // A final message may lack the newline
+                if self.buffer.is_empty() {
+                    return Ok(None);
+                }
+                break self.buffer.len();
+            }
+
+            self.buffer.extend_from_slice(&chunk[..read]);
+        };
+
+        // Logged after parsing, as `Debug` redacts tokens but the raw JSON
+        // does not
+        let message = std::str::from_utf8(&self.buffer[..end])
+            .map_err(|_| Error::InvalidUtf8)
+            .and_then(|json| Ok(Message::deserialize(json)?));
+        self.consume(end + 1);
+
+        let message = message?;
+        debug!("Received: {message:?}");
 
         Ok(Some(message))
     }
 
     pub fn message<M: Into<Message>>(&mut self, message: M) -> Result<()> {
         let message = message.into();
-        let json = message.serialize()?;
-        debug!("Sending: {json}");
+        debug!("Sending: {message:?}");
+
+        let mut json = Zeroizing::new(Vec::with_capacity(BUFFER_SIZE));
+        message.serialize_into(&mut *json)?;
+        json.push(b'\n');
 
-        self.writer.write_all(json.as_bytes())?;
-        self.writer.write_all(b"\n")?;
-        self.writer.flush()?;
+        self.stream.write_all(&json)?;
+        self.stream.flush()?;
 
         Ok(())
     }
 
+    /// Remove the first `len` bytes from the buffer, zeroing the memory they
+    /// occupied.
+    fn consume(&mut self, len: usize) {
+        let len = len.min(self.buffer.len());
+        self.buffer[..len].zeroize();
+        self.buffer.drain(..len);
+
+        // Zero what the remaining bytes were moved from
+        let remaining = self.buffer.len();
+        let capacity = self.buffer.capacity();
+        self.buffer.resize(capacity, 0);
+        self.buffer.truncate(remaining);
+    }
+
     pub fn as_raw_fd(&self) -> i32 {
-        self.reader.get_ref().as_raw_fd()
+        self.stream.as_raw_fd()
     }
 }
 
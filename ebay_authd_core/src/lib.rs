@@ -6,9 +6,12 @@
 
 use serde::{Deserialize, Serialize};
 pub use serde_json::Error as SerializeError;
+use std::io::Write;
 
 pub mod request;
 pub mod response;
+pub mod secret;
+pub mod state;
 
 #[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
 pub enum Message {
@@ -21,6 +24,11 @@ impl Message {
         Ok(serde_json::to_string(&self)?.into())
     }
 
+    /// Serialize into `writer` without an intermediate string.
+    pub fn serialize_into<W: Write>(&self, writer: W) -> serde_json::Result<()> {
+        serde_json::to_writer(writer, self)
+    }
+
     pub fn deserialize(json: &str) -> serde_json::Result<Self> {
         serde_json::from_str(json)
     }
@@ -1,12 +1,25 @@
-use crate::Message;
+use crate::{secret::Secret, Message};
 use serde::{Deserialize, Serialize};
+use std::time::Duration;
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
 pub enum Request {
     Status,
     Token,
+    /// Like `Token`, but waits up to the given time for the daemon to become ready.
+    AwaitToken(Duration),
     ForceRefresh,
     Stop,
+    BeginAuth,
+    /// The redirect URL (or authorization code) from the consent page.
+    CompleteAuth(Secret),
+    /// Revoke the grant and forget all tokens.
+    Revoke,
+    /// Reload the configuration.
+    Reload,
+    /// Change the log level (`off`, `error`, `warn`, `info`, `debug` or
+    /// `trace`) until the daemon stops.
+    SetLogLevel(Box<str>),
 }
 
 impl From<Request> for Message {
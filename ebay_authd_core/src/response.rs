@@ -1,4 +1,4 @@
-use crate::Message;
+use crate::{secret::Secret, state::State, Message};
 use serde::{Deserialize, Serialize};
 use std::time::Duration;
 
@@ -6,12 +6,40 @@ use std::time::Duration;
 pub enum Response {
     Status {
         version: Box<str>,
-        expiry: Duration,
-        last_refresh: Duration,
-        short_token: Box<str>,
-        short_refresh_token: Box<str>,
+        #[serde(default)]
+        pid: u32,
+        state: State,
+        expiry: Option<Duration>,
+        last_refresh: Option<Duration>,
+        /// Fingerprint of the access token, see `README.md`
+        token_fingerprint: Option<Box<str>>,
+        /// Fingerprint of the refresh token
+        refresh_token_fingerprint: Option<Box<str>>,
     },
-    Token(Box<str>),
+    Token(Secret),
+    /// No token is available in the current state.
+    NotReady(State),
+    AuthUrl(Box<str>),
+    Authorized,
+    /// Completing the authorization failed. If `restart` is set, the
+    /// authorization is no longer pending and must be started again.
+    AuthFailed {
+        reason: Box<str>,
+        restart: bool,
+    },
+    /// The grant was forgotten. `provider` is set if it was also revoked
+    /// at eBay.
+    Revoked {
+        provider: bool,
+    },
+    /// The configuration was reloaded. Changes to the listed fields only
+    /// take effect after a restart.
+    Reloaded {
+        restart_required: Vec<Box<str>>,
+    },
+    /// The log level now in effect.
+    LogLevel(Box<str>),
+    Error(Box<str>),
 }
 
 impl From<Response> for Message {
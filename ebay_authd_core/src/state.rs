@@ -0,0 +1,37 @@
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+/// Lifecycle state of the daemon.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub enum State {
+    /// No token and no authorization in progress.
+    Unauthenticated,
+    /// Waiting for the user to complete consent.
+    Authorizing,
+    /// Holding a valid token.
+    Ready,
+    /// Requesting a new access token.
+    Refreshing,
+    /// The last refresh failed, it will be retried.
+    Degraded,
+    /// The refresh token was rejected, a new authorization is needed.
+    ReauthRequired,
+    /// Shutting down.
+    Stopping,
+}
+
+impl fmt::Display for State {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Self::Unauthenticated => "Unauthenticated",
+            Self::Authorizing => "Authorizing",
+            Self::Ready => "Ready",
+            Self::Refreshing => "Refreshing",
+            Self::Degraded => "Degraded",
+            Self::ReauthRequired => "Reauthentication required",
+            Self::Stopping => "Stopping",
+        };
+
+        f.write_str(name)
+    }
+}